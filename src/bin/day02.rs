@@ -1,137 +1,231 @@
 
-use std::cmp::{max, min};
-use std::io::BufRead;
-
-#[derive(Clone, Copy, Debug)]
-struct Bounds(u8, u8);
+use std::io::{BufRead, Cursor, Read};
 
 trait Puzzle {
     fn init_state(&self) -> State;
-    fn bounds(&self, b: u8) -> Bounds;
-    fn value(&self, s: &State) -> char;
+    fn value(&self, s: &State) -> Option<&str>;
 }
 
-fn sub1<P: Puzzle>(a: u8, b: u8, p: &P) -> u8 {
-    let Bounds(low, _) = p.bounds(b);
-    max(low as i8, (a as i8) - 1) as u8
-}
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct State(usize, usize);
 
-fn add1<P: Puzzle>(a: u8, b: u8, p: &P) -> u8 {
-    let Bounds(_, hi) = p.bounds(b);
-    min(hi, a + 1)
+impl State {
+    fn step(&self, c: char) -> Option<State> {
+        match c {
+            'U' => self.1.checked_sub(1).map(|y| State(self.0, y)),
+            'D' => self.1.checked_add(1).map(|y| State(self.0, y)),
+            'L' => self.0.checked_sub(1).map(|x| State(x, self.1)),
+            'R' => self.0.checked_add(1).map(|x| State(x, self.1)),
+            _ => panic!("Unknown character")
+        }
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
-struct State(u8, u8);
-
-fn decode<P: Puzzle>(p: &P, mut state: State, st: &str) -> (State, char) {
+fn decode<P: Puzzle + ?Sized>(p: &P, mut state: State, st: &str) -> (State, String) {
     for c in st.chars() {
-        state = match c {
-            'U' => State(state.0, sub1(state.1, state.0, p)),
-            'D' => State(state.0, add1(state.1, state.0, p)),
-            'L' => State(sub1(state.0, state.1, p), state.1),
-            'R' => State(add1(state.0, state.1, p), state.1),
-            _ => panic!("Unknown character")
-        };
+        if let Some(next) = state.step(c) {
+            if p.value(&next).is_some() {
+                state = next
+            }
+        }
     }
-    (state, p.value(&state))
+    (state, p.value(&state).expect("state off the keypad").to_string())
+}
+
+fn decode_lines<P: Puzzle>(p: &P, input: &mut dyn BufRead) -> Result<String, String> {
+    decode_fingers(&[p], '|', input).map(|mut codes| codes.remove(0))
 }
 
-fn decode_lines<P: Puzzle>(p: &P, input: &mut BufRead) -> String {
-    let mut state = p.init_state();
-    let mut code = String::new();
+/// Decode lines where each line holds one set of moves per finger, split on
+/// `sep`.  Every finger works its own keypad and keeps its own state between
+/// lines, so each line emits one key per finger.  Returns the code for each
+/// finger.
+fn decode_fingers(ps: &[&dyn Puzzle], sep: char, input: &mut dyn BufRead) -> Result<Vec<String>, String> {
+    let mut states: Vec<State> = ps.iter().map(|p| p.init_state()).collect();
+    let mut codes: Vec<String> = ps.iter().map(|_| String::new()).collect();
+
+    for (n, line) in input.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let moves: Vec<&str> = line.split(sep).map(|m| m.trim()).collect();
+        if moves.len() != ps.len() {
+            return Err(format!("line {}: expected {} fingers, got {}", n + 1, ps.len(), moves.len()))
+        }
 
-    for line in input.lines() {
-        let (s, v) = decode(p, state, &line.unwrap());
-        state = s;
-        println!("{:?} {}", state, v);
-        code.push(v)
+        let mut keys = Vec::with_capacity(ps.len());
+        for (i, m) in moves.iter().enumerate() {
+            let (s, v) = decode(ps[i], states[i], m);
+            states[i] = s;
+            codes[i].push_str(&v);
+            keys.push(v);
+        }
+        let shown: Vec<String> = states.iter().map(|s| format!("{:?}", s)).collect();
+        println!("{} {}", shown.join(" "), keys.join(" "));
     }
-    code
+    Ok(codes)
 }
 
+/// A keypad laid out on a grid.  Rows may be of different lengths and any
+/// position without a key is a hole that the finger can't move onto.
+#[derive(Clone, Debug)]
+struct Keypad {
+    keys: Vec<Vec<Option<String>>>,
+    start: State
+}
 
-#[derive(Clone, Copy, Debug)]
-struct Part1Puzzle { }
-
-static P1_KEYS: &'static str = "123456789";
+impl Keypad {
+    /// Build a keypad from a layout with one row per line and keys separated
+    /// by whitespace.  A key of `.` marks a hole.
+    fn new(layout: &str, start: State) -> Result<Keypad, String> {
+        let keys = layout.lines()
+                         .map(|row| row.split_whitespace()
+                                       .map(|k| if k == "." { None } else { Some(k.to_string()) })
+                                       .collect())
+                         .collect();
+        let keypad = Keypad { keys, start };
+
+        if keypad.value(&start).is_none() {
+            return Err(format!("no key at starting position {:?}", start))
+        }
+        Ok(keypad)
+    }
+}
 
-impl Puzzle for Part1Puzzle {
+impl Puzzle for Keypad {
     fn init_state(&self) -> State {
-        State(1, 1)
+        self.start
     }
 
-    #[allow(unused_variables)]
-    fn bounds(&self, b: u8) -> Bounds {
-        Bounds(0, 2)
+    fn value(&self, s: &State) -> Option<&str> {
+        self.keys.get(s.1)
+                 .and_then(|row| row.get(s.0))
+                 .and_then(|k| k.as_ref())
+                 .map(|k| k.as_str())
     }
+}
 
-    fn value(&self, s: &State) -> char {
-        let i = s.0 + s.1 * 3;
-        P1_KEYS.chars().nth(i as usize).unwrap()
-    }
+static P1_KEYS: &str = "1 2 3
+                        4 5 6
+                        7 8 9";
+
+fn part1_keypad() -> Keypad {
+    Keypad::new(P1_KEYS, State(1, 1)).unwrap()
 }
 
 #[test]
 fn aoc02_test1() {
-    use std::io::Cursor;
     let input = "ULL\nRRDDD\nLURDL\nUUUUD";
     let mut input = Cursor::new(input);
-    let p = Part1Puzzle { };
-    let code = decode_lines(&p, &mut input);
+    let p = part1_keypad();
+    let code = decode_lines(&p, &mut input).unwrap();
     assert!(code == "1985");
 }
 
-#[derive(Clone, Copy, Debug)]
-struct Part2Puzzle { }
-
-static P2_KEYS: &'static str = "XX1XXX234X56789XABCXXXDXX";
-
-impl Puzzle for Part2Puzzle {
-    fn init_state(&self) -> State {
-        State(0, 2)
-    }
+static P2_KEYS: &str = ". . 1 . .
+                        . 2 3 4 .
+                        5 6 7 8 9
+                        . A B C .
+                        . . D . .";
 
-    #[allow(unused_variables)]
-    fn bounds(&self, b: u8) -> Bounds {
-        let b = (b as i8 - 2).abs();
-        match b {
-            0 => Bounds(0, 4),
-            1 => Bounds(1, 3),
-            2 => Bounds(2, 2),
-            _ => panic!("shouldn't happen {}", b)
-        }
-    }
-
-    fn value(&self, s: &State) -> char {
-        let i = s.0 + s.1 * 5;
-        P2_KEYS.chars().nth(i as usize).unwrap()
-    }
+fn part2_keypad() -> Keypad {
+    Keypad::new(P2_KEYS, State(0, 2)).unwrap()
 }
 
 #[test]
 fn aoc02_test2() {
-    use std::io::Cursor;
     let input = "ULL\nRRDDD\nLURDL\nUUUUD";
     let mut input = Cursor::new(input);
-    let p = Part2Puzzle { };
-    let code = decode_lines(&p, &mut input);
+    let p = part2_keypad();
+    let code = decode_lines(&p, &mut input).unwrap();
     assert!(code == "5DB3");
 }
 
+#[test]
+fn aoc02_test_sparse_keypad() {
+    let layout = "1 2 3 4
+                  5 . . 6
+                  7 . . 8
+                  9 10 OK";
+    let p = Keypad::new(layout, State(0, 0)).unwrap();
+    let mut input = Cursor::new("DDD\nR\nRR\nLLUUU");
+    let code = decode_lines(&p, &mut input).unwrap();
+    assert_eq!(code, "910OK1");
+    assert!(Keypad::new(layout, State(1, 1)).is_err());
+}
+
+#[test]
+fn aoc02_test_wide_keypad() {
+    let row: Vec<String> = (0..300).map(|i| i.to_string()).collect();
+    let p = Keypad::new(&row.join(" "), State(0, 0)).unwrap();
+    let moves: String = (0..299).map(|_| 'R').collect();
+    let (state, v) = decode(&p, p.init_state(), &moves);
+    assert_eq!(state, State(299, 0));
+    assert_eq!(v, "299");
+}
+
+#[test]
+fn aoc02_test_multi_finger() {
+    let p1 = part1_keypad();
+    let p2 = part2_keypad();
+    let ps: Vec<&dyn Puzzle> = vec![&p1, &p2];
+    let input = "ULL | ULL\nRRDDD | RRDDD\nLURDL | LURDL\nUUUUD | UUUUD";
+    let mut input = Cursor::new(input);
+    let codes = decode_fingers(&ps, '|', &mut input).unwrap();
+    assert_eq!(codes, vec!["1985", "5DB3"]);
+
+    let mut input = Cursor::new("ULL | ULL\nRRDDD");
+    assert!(decode_fingers(&ps, '|', &mut input).is_err());
+    assert!(keypad("2").is_ok());
+    assert!(keypad("3").is_err());
+}
+
 fn solve<P: Puzzle>(p: &P) {
     let stdin = std::io::stdin();
     let mut input = stdin.lock();
-    let code = decode_lines(p, &mut input);
-    println!("{}", code);
+    match decode_lines(p, &mut input) {
+        Ok(code) => println!("{}", code),
+        Err(e) => println!("error: {}", e)
+    }
+}
+
+/// The keypad for part `1` or `2` of the puzzle.
+fn keypad(part: &str) -> Result<Keypad, String> {
+    match part {
+        "1" => Ok(part1_keypad()),
+        "2" => Ok(part2_keypad()),
+        _ => Err(format!("expected keypad '1' or '2', got {:?}", part))
+    }
+}
+
+/// Decode several fingers at once, with `parts` naming each finger's
+/// keypad in order.  Every finger uses the part one keypad when none are
+/// named.
+fn solve_fingers(parts: &[String]) -> Result<Vec<String>, String> {
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+    let mut first = String::new();
+    input.read_line(&mut first).map_err(|e| e.to_string())?;
+
+    let keypads = if parts.is_empty() {
+        first.split('|').map(|_| part1_keypad()).collect()
+    } else {
+        parts.iter().map(|p| keypad(p)).collect::<Result<Vec<Keypad>, String>>()?
+    };
+    let ps: Vec<&dyn Puzzle> = keypads.iter().map(|p| p as &dyn Puzzle).collect();
+    let mut input = Cursor::new(first).chain(input);
+    decode_fingers(&ps, '|', &mut input)
 }
 
 fn main() {
-    let flag = std::env::args().nth(1).unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = args.first().expect("expected '-1', '-2' or '-m'");
 
     match flag.as_ref() {
-        "-1" => solve(&Part1Puzzle { }),
-        "-2" => solve(&Part2Puzzle { }),
-        _ => println!("expected '-1' or '-2'")
+        "-1" => solve(&part1_keypad()),
+        "-2" => solve(&part2_keypad()),
+        "-m" => match solve_fingers(&args[1..]) {
+            Ok(codes) => println!("{}", codes.join(" ")),
+            Err(e) => println!("error: {}", e)
+        },
+        _ => println!("expected '-1', '-2' or '-m'")
     }
 }
@@ -1,82 +1,670 @@
 
-extern crate itertools;
+extern crate num_bigint;
 
+use std::cmp::Ordering;
+use std::fmt;
 use std::io::BufRead;
 use std::str::FromStr;
 
-use itertools::Itertools;
+use num_bigint::BigUint;
 
-#[derive(Clone, Copy, Debug)]
-struct Triangle(u32, u32, u32);
+/// A type that side lengths can be measured in.
+trait Side: Clone + Default + Ord + FromStr + fmt::Display + fmt::Debug {
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn to_big(&self) -> BigUint;
+}
+
+macro_rules! fixed_width_side {
+    ($($t:ty),*) => {
+        $(impl Side for $t {
+            fn checked_add(&self, other: &$t) -> Option<$t> {
+                <$t>::checked_add(*self, *other)
+            }
+
+            fn to_big(&self) -> BigUint {
+                BigUint::from(*self)
+            }
+        })*
+    }
+}
+
+fixed_width_side!(u32, u64, u128);
+
+impl Side for BigUint {
+    fn checked_add(&self, other: &BigUint) -> Option<BigUint> {
+        Some(self + other)
+    }
+
+    fn to_big(&self) -> BigUint {
+        self.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Polygon<T>(Vec<T>);
+
+impl<T> Polygon<T> {
+    fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.0.iter()
+    }
+}
+
+impl<T: Side> Polygon<Length<T>> {
+    /// The polygon with plain side lengths, or `None` if any side was
+    /// negative.
+    fn unsigned(&self) -> Option<Polygon<T>> {
+        self.iter().map(|l| l.unsigned()).collect::<Option<_>>().map(Polygon)
+    }
+}
 
-impl Triangle {
-    fn iter(&self) -> std::vec::IntoIter<u32> {
-        vec!(self.0, self.1, self.2).into_iter()
+impl<T: fmt::Display> fmt::Display for Polygon<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sides: Vec<String> = self.iter().map(|s| s.to_string()).collect();
+        f.write_str(&sides.join(" "))
     }
 }
 
-impl FromStr for Triangle {
+impl<T: Side> FromStr for Polygon<T> {
     type Err = String;
 
-    fn from_str(s: &str) -> Result<Triangle, Self::Err> {
-        let (a, b, c) = try!(parse_line(s));
-        Ok(Triangle(a, b, c))
+    fn from_str(s: &str) -> Result<Polygon<T>, Self::Err> {
+        let n = s.split_whitespace().count();
+        Polygon(parse_line(s, n)?).unsigned().ok_or(format!("negative side in {:?}", s))
+    }
+}
+
+/// A side length as it was read.  A negative length keeps its magnitude so
+/// it can be reported as written, and makes any polygon it is part of
+/// invalid.
+#[derive(Clone, Debug, PartialEq)]
+enum Length<T> {
+    Plain(T),
+    Negative(T)
+}
+
+impl<T: Side> Length<T> {
+    fn unsigned(&self) -> Option<T> {
+        match *self {
+            Length::Plain(ref t) => Some(t.clone()),
+            Length::Negative(_) => None
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Length<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Length::Plain(ref t) => write!(f, "{}", t),
+            Length::Negative(ref t) => write!(f, "-{}", t)
+        }
     }
 }
 
-fn parse_line(s: &str) -> Result<(u32, u32, u32), String> {
+fn parse_side<T: Side>(s: &str) -> Result<Length<T>, String> {
+    let (abs, length): (&str, fn(T) -> Length<T>) = match s.strip_prefix('-') {
+        Some(abs) => (abs, Length::Negative),
+        None => (s, Length::Plain)
+    };
+    abs.parse().map(length).map_err(|_| format!("expected integer string that fits the side width, got {:?}", s))
+}
+
+fn parse_line<T: Side>(s: &str, n: usize) -> Result<Vec<Length<T>>, String> {
     let nums_str: Vec<&str> = s.split_whitespace().collect();
+    parse_fields(&nums_str, n)
+}
 
-    if nums_str.len() == 3 {
-        let nums: Vec<u32> = nums_str.iter()
-                                       .map(|s| s.parse().expect("expected integer string"))
-                                       .collect();
-        Ok((nums[0], nums[1], nums[2]))
+fn parse_fields<T: Side>(nums_str: &[&str], n: usize) -> Result<Vec<Length<T>>, String> {
+    if nums_str.len() == n {
+        nums_str.iter()
+                .map(|s| parse_side(s))
+                .collect()
     } else {
-        Err("Expected 3 space separated values".to_string())
+        Err(format!("Expected {} separated values", n))
     }
 }
 
-fn is_triangle(t: Triangle) -> bool {
-    2 * t.iter().max().unwrap() < t.iter().sum()
+/// How the fields of an input line are separated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Delimiter {
+    Whitespace,
+    /// A single character, as in CSV or TSV exports.
+    Char(char),
+    /// Any run of whitespace, commas or semicolons.
+    Mixed
 }
 
-fn part_one() {
-    let stdin = std::io::stdin();
-    let count = stdin.lock()
-                     .lines()
-                     .map(|r| r.expect("expected line of data"))
-                     .map(|l| parse_line(&l).expect("expected three integers"))
-                     .map(|(a, b, c)| Triangle(a, b, c))
-                     .filter(|t| is_triangle(*t))
-                     .count();
-    println!("{}", count);
+impl Delimiter {
+    fn split<'a>(&self, s: &'a str) -> Vec<&'a str> {
+        match *self {
+            Delimiter::Whitespace => s.split_whitespace().collect(),
+            Delimiter::Char(c) => s.split(c).map(clean_field).collect(),
+            Delimiter::Mixed => s.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+                                 .filter(|f| !f.is_empty())
+                                 .map(clean_field)
+                                 .collect()
+        }
+    }
+}
+
+/// Strip the padding and quoting spreadsheets put around a field.
+fn clean_field(s: &str) -> &str {
+    s.trim().trim_matches('"').trim()
+}
+
+/// A column picked out of each line, either by its 1-based position or by
+/// its name in the header row.
+#[derive(Clone, Debug, PartialEq)]
+enum Column {
+    Index(usize),
+    Name(String)
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Column, String> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("columns are numbered from 1".to_string()),
+            Ok(i) => Ok(Column::Index(i - 1)),
+            Err(_) => Ok(Column::Name(s.to_string()))
+        }
+    }
+}
+
+/// Describes how the side lengths are laid out in each input line.
+#[derive(Clone, Debug)]
+struct Format {
+    delim: Delimiter,
+    header: bool,
+    /// The columns to read, in order.  Every column is read when empty.
+    columns: Vec<Column>
+}
+
+impl Format {
+    fn whitespace() -> Format {
+        Format { delim: Delimiter::Whitespace, header: false, columns: Vec::new() }
+    }
+
+    /// Replace the named columns with their positions in `header`.
+    fn resolve(&mut self, header: &str) -> Result<(), String> {
+        let names = self.delim.split(header);
+        for c in self.columns.iter_mut() {
+            if let Column::Name(ref name) = *c {
+                let i = names.iter()
+                             .position(|n| n == name)
+                             .ok_or(format!("no column named {:?} in header", name))?;
+                *c = Column::Index(i)
+            }
+        }
+        Ok(())
+    }
+
+    fn fields<'a>(&self, line: &'a str) -> Result<Vec<&'a str>, String> {
+        let fields = self.delim.split(line);
+        if self.columns.is_empty() {
+            return Ok(fields)
+        }
+
+        self.columns
+            .iter()
+            .map(|c| match *c {
+                Column::Index(i) => fields.get(i).cloned().ok_or(format!("missing column {}", i + 1)),
+                Column::Name(ref name) => Err(format!("column {:?} needs a header row", name))
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Validity {
+    Valid,
+    /// The longest side is exactly the sum of the others, so the polygon is
+    /// flat.
+    Degenerate,
+    Invalid
 }
 
-fn part_two() {
+/// A polygon is valid when its longest side is shorter than the sum of the
+/// others.  Zero length sides are never valid.
+fn validity<T: Side>(p: &Polygon<T>) -> Validity {
+    let zero = T::default();
+    let (i, max) = match p.iter().enumerate().max_by_key(|&(_, s)| s) {
+        Some(max) => max,
+        None => return Validity::Invalid
+    };
+    if p.iter().any(|s| *s == zero) {
+        return Validity::Invalid
+    }
+
+    let mut rest = zero;
+    for (_, s) in p.iter().enumerate().filter(|&(j, _)| j != i) {
+        rest = match rest.checked_add(s) {
+            Some(r) => r,
+            // the sum no longer fits in T, so it is longer than any side
+            None => return Validity::Valid
+        };
+    }
+
+    match rest.cmp(max) {
+        Ordering::Greater => Validity::Valid,
+        Ordering::Equal => Validity::Degenerate,
+        Ordering::Less => Validity::Invalid
+    }
+}
+
+fn is_polygon<T: Side>(p: &Polygon<T>) -> bool {
+    validity(p) == Validity::Valid
+}
+
+/// Tally of the polygons seen, with a breakdown of the valid triangles.
+#[derive(Debug, Default)]
+struct Report<T> {
+    valid: usize,
+    equilateral: usize,
+    isosceles: usize,
+    scalene: usize,
+    right: usize,
+    degenerate: usize,
+    invalid: Vec<(Lines, Polygon<Length<T>>)>
+}
+
+impl<T: Side> Report<T> {
+    fn add(&mut self, lines: Lines, read: Polygon<Length<T>>) {
+        let p = match read.unsigned() {
+            Some(p) => p,
+            None => return self.invalid.push((lines, read))
+        };
+        match validity(&p) {
+            Validity::Valid => {
+                self.valid += 1;
+                if p.0.len() == 3 {
+                    self.add_triangle(&p)
+                }
+            }
+            Validity::Degenerate => self.degenerate += 1,
+            Validity::Invalid => self.invalid.push((lines, read))
+        }
+    }
+
+    fn add_triangle(&mut self, p: &Polygon<T>) {
+        let mut t: Vec<BigUint> = p.iter().map(|s| s.to_big()).collect();
+        t.sort();
+
+        if t[0] == t[2] {
+            self.equilateral += 1
+        } else if t[0] == t[1] || t[1] == t[2] {
+            self.isosceles += 1
+        } else {
+            self.scalene += 1
+        }
+
+        if &t[0] * &t[0] + &t[1] * &t[1] == &t[2] * &t[2] {
+            self.right += 1
+        }
+    }
+}
+
+impl<T: Side> fmt::Display for Report<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "valid: {}", self.valid)?;
+        writeln!(f, "  equilateral: {}", self.equilateral)?;
+        writeln!(f, "  isosceles: {}", self.isosceles)?;
+        writeln!(f, "  scalene: {}", self.scalene)?;
+        writeln!(f, "  right-angled: {}", self.right)?;
+        writeln!(f, "degenerate: {}", self.degenerate)?;
+        writeln!(f, "invalid: {}", self.invalid.len())?;
+        for (lines, p) in &self.invalid {
+            writeln!(f, "  {}: {}", lines, p)?;
+        }
+        Ok(())
+    }
+}
+
+/// The 1-based range of input lines a polygon was read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Lines(usize, usize);
+
+impl fmt::Display for Lines {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == self.1 {
+            write!(f, "line {}", self.0)
+        } else {
+            write!(f, "lines {}-{}", self.0, self.1)
+        }
+    }
+}
+
+/// How polygons are read out of a block of rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Layout {
+    /// Every row is a polygon.
+    Rows,
+    /// Every column of a block is a polygon.
+    Columns,
+    /// Polygon `k` of a block takes column `(i + k) % width` from row `i`.
+    Diagonals
+}
+
+/// Read polygons out of `rows` in blocks of `height` rows.  A block as wide
+/// as `width` columns yields `width` polygons of `height` sides each.  Every
+/// row comes with its line number, so each polygon comes with the lines it
+/// was read from.
+fn read_polygons<T: Clone>(rows: &[(usize, Vec<T>)], height: usize, layout: Layout) -> Result<Vec<(Lines, Polygon<T>)>, String> {
+    if layout == Layout::Rows {
+        return Ok(rows.iter()
+                      .map(|(n, row)| (Lines(*n, *n), Polygon(row.clone())))
+                      .collect())
+    }
+    if height == 0 || !rows.len().is_multiple_of(height) {
+        return Err(format!("{} lines is not a multiple of the group size {}", rows.len(), height))
+    }
+
+    let mut polygons = Vec::new();
+    for block in rows.chunks(height) {
+        let lines = Lines(block[0].0, block[height - 1].0);
+        let width = block[0].1.len();
+        if block.iter().any(|(_, row)| row.len() != width) {
+            return Err(format!("rows in {} differ in width", lines))
+        }
+
+        for k in 0..width {
+            let sides = block.iter()
+                             .enumerate()
+                             .map(|(i, (_, row))| match layout {
+                                 Layout::Diagonals => row[(i + k) % width].clone(),
+                                 _ => row[k].clone()
+                             })
+                             .collect();
+            polygons.push((lines, Polygon(sides)))
+        }
+    }
+    Ok(polygons)
+}
+
+#[test]
+fn aoc03_test_parse_line() {
+    use Length::Plain;
+    assert_eq!(parse_line("  5  10  25", 3), Ok(vec![Plain(5u32), Plain(10), Plain(25)]));
+    assert_eq!(parse_line("1 2 3 4", 4), Ok(vec![Plain(1u32), Plain(2), Plain(3), Plain(4)]));
+    assert!(parse_line::<u32>("1 2 3 4", 3).is_err());
+    assert!(parse_line::<u32>("1 x 3", 3).is_err());
+    assert_eq!("3 4 5 6".parse(), Ok(Polygon(vec![3u32, 4, 5, 6])));
+}
+
+#[test]
+fn aoc03_test_is_polygon() {
+    assert!(!is_polygon(&Polygon(vec![5u32, 10, 25])));
+    assert!(is_polygon(&Polygon(vec![3u32, 4, 5])));
+    assert!(!is_polygon(&Polygon(vec![1u32, 2, 3])));
+    assert!(is_polygon(&Polygon(vec![1u32, 1, 1, 2])));
+    assert!(!is_polygon(&Polygon(vec![1u32, 1, 1, 3])));
+    assert!(!is_polygon(&Polygon::<u32>(vec![])));
+}
+
+/// Rows numbered from line 1, as if read from input with no gaps.
+#[cfg(test)]
+fn numbered<T: Clone>(rows: &[Vec<T>]) -> Vec<(usize, Vec<T>)> {
+    rows.iter().cloned().enumerate().map(|(i, row)| (i + 1, row)).collect()
+}
+
+#[test]
+fn aoc03_test_read_columns() {
+    let rows: Vec<Vec<u32>> = vec![vec![101, 301, 501],
+                                   vec![102, 302, 502],
+                                   vec![103, 303, 503]];
+    let ps: Vec<Polygon<u32>> = read_polygons(&numbered(&rows), 3, Layout::Columns).unwrap()
+                                                                        .into_iter()
+                                                                        .map(|(_, p)| p)
+                                                                        .collect();
+    assert_eq!(ps, vec![Polygon(vec![101, 102, 103]),
+                        Polygon(vec![301, 302, 303]),
+                        Polygon(vec![501, 502, 503])]);
+
+    let rows: Vec<Vec<u32>> = vec![vec![1, 2], vec![3, 4], vec![5, 6], vec![7, 8]];
+    let ps = read_polygons(&numbered(&rows), 2, Layout::Columns).unwrap();
+    assert_eq!(ps, vec![(Lines(1, 2), Polygon(vec![1, 3])), (Lines(1, 2), Polygon(vec![2, 4])),
+                        (Lines(3, 4), Polygon(vec![5, 7])), (Lines(3, 4), Polygon(vec![6, 8]))]);
+}
+
+#[test]
+fn aoc03_test_read_diagonals() {
+    let rows: Vec<Vec<u32>> = vec![vec![1, 2, 3],
+                                   vec![4, 5, 6],
+                                   vec![7, 8, 9]];
+    let ps: Vec<Polygon<u32>> = read_polygons(&numbered(&rows), 3, Layout::Diagonals).unwrap()
+                                                                          .into_iter()
+                                                                          .map(|(_, p)| p)
+                                                                          .collect();
+    assert_eq!(ps, vec![Polygon(vec![1, 5, 9]),
+                        Polygon(vec![2, 6, 7]),
+                        Polygon(vec![3, 4, 8])]);
+}
+
+#[test]
+fn aoc03_test_read_leftover_lines() {
+    let rows: Vec<Vec<u32>> = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9], vec![1, 1, 1]];
+    let rows = numbered(&rows);
+    assert!(read_polygons(&rows, 3, Layout::Columns).is_err());
+    assert!(read_polygons(&rows, 3, Layout::Diagonals).is_err());
+    assert_eq!(read_polygons(&rows, 3, Layout::Rows).unwrap().len(), 4);
+}
+
+#[test]
+fn aoc03_test_validity() {
+    assert_eq!(validity(&Polygon(vec![3u32, 4, 5])), Validity::Valid);
+    assert_eq!(validity(&Polygon(vec![1u32, 2, 3])), Validity::Degenerate);
+    assert_eq!(validity(&Polygon(vec![5u32, 10, 25])), Validity::Invalid);
+}
+
+#[test]
+fn aoc03_test_report() {
+    let rows: Vec<Vec<u32>> = vec![vec![3, 4, 5],
+                                   vec![2, 2, 2],
+                                   vec![2, 2, 3],
+                                   vec![1, 2, 3],
+                                   vec![5, 10, 25],
+                                   vec![4, 5, 6]];
+    let mut report = Report::default();
+    for (lines, p) in read_polygons(&numbered(&rows), 1, Layout::Rows).unwrap() {
+        report.add(lines, Polygon(p.iter().cloned().map(Length::Plain).collect()))
+    }
+    assert_eq!(report.valid, 4);
+    assert_eq!(report.equilateral, 1);
+    assert_eq!(report.isosceles, 1);
+    assert_eq!(report.scalene, 2);
+    assert_eq!(report.right, 1);
+    assert_eq!(report.degenerate, 1);
+    assert_eq!(report.invalid[0].0, Lines(5, 5));
+    assert_eq!(report.invalid[0].1.unsigned(), Some(Polygon(vec![5, 10, 25])));
+    assert!(format!("{}", report).contains("  line 5: 5 10 25\n"));
+}
+
+#[test]
+fn aoc03_test_report_line_numbers() {
+    use std::io::Cursor;
+    let report = |input: &str, mut format: Format, height: usize, layout: Layout| {
+        let rows = read_rows::<u32>(&mut Cursor::new(input), 3, &mut format).unwrap();
+        let mut r = Report::default();
+        for (lines, p) in read_polygons(&rows, height, layout).unwrap() {
+            r.add(lines, p)
+        }
+        r.to_string()
+    };
+    let r = report("3 4 5\n\n5 10 25\n", Format::whitespace(), 3, Layout::Rows);
+    assert!(r.contains("  line 3: 5 10 25\n"));
+
+    let csv = Format { delim: Delimiter::Char(','), header: true, columns: Vec::new() };
+    let r = report("a,b,c\n3,4,5\n\n5,10,25\n", csv, 3, Layout::Rows);
+    assert!(r.contains("  line 4: 5 10 25\n"));
+
+    let r = report("5 3 1\n\n10 4 1\n25 5 1\n", Format::whitespace(), 3, Layout::Columns);
+    assert!(r.contains("  lines 1-4: 5 10 25\n"));
+}
+
+#[test]
+fn aoc03_test_non_positive_sides() {
+    use Length::{Negative, Plain};
+    let read = Polygon(parse_line("3 -4 5", 3).unwrap());
+    assert_eq!(read, Polygon(vec![Plain(3u32), Negative(4), Plain(5)]));
+    assert_eq!(read.unsigned(), None);
+    assert!(parse_line::<u32>("3 - 5", 3).is_err());
+    assert!("3 -4 5".parse::<Polygon<u32>>().is_err());
+
+    let mut report = Report::default();
+    report.add(Lines(1, 1), read);
+    assert_eq!(report.invalid.len(), 1);
+    assert!(format!("{}", report).contains("  line 1: 3 -4 5\n"));
+    assert_eq!(validity(&Polygon(vec![0u32, 4, 5])), Validity::Invalid);
+    assert_eq!(validity(&Polygon(vec![0u32, 0, 0])), Validity::Invalid);
+}
+
+#[test]
+fn aoc03_test_wide_sides() {
+    assert!(parse_line::<u32>("4294967296 1 1", 3).is_err());
+    assert_eq!(validity(&Polygon(vec![u32::MAX, u32::MAX, u32::MAX])), Validity::Valid);
+    assert_eq!(validity(&Polygon(vec![u64::MAX, u64::MAX - 1, 1])), Validity::Degenerate);
+    assert_eq!(validity(&Polygon(vec![u128::MAX, u128::MAX, 2])), Validity::Valid);
+    assert_eq!(validity(&Polygon(vec![u128::MAX, 1, 1])), Validity::Invalid);
+
+    let huge = "340282366920938463463374607431768211456000";
+    let p: Polygon<BigUint> = format!("{} {} 1", huge, huge).parse().unwrap();
+    assert_eq!(validity(&p), Validity::Valid);
+
+    let mut report = Report::default();
+    report.add(Lines(1, 1), Polygon(vec![Length::Plain(u128::MAX / 2); 3]));
+    assert_eq!(report.equilateral, 1);
+}
+
+#[test]
+fn aoc03_test_delimiters() {
+    assert_eq!(Delimiter::Char(',').split("3, \"4\",5"), vec!["3", "4", "5"]);
+    assert_eq!(Delimiter::Char('\t').split("3\t\t5"), vec!["3", "", "5"]);
+    assert_eq!(Delimiter::Mixed.split(" 3,4;\t5  6 "), vec!["3", "4", "5", "6"]);
+}
+
+#[test]
+fn aoc03_test_read_csv() {
+    use std::io::Cursor;
+    let mut format = Format {
+        delim: Delimiter::Char(','),
+        header: true,
+        columns: vec!["c".parse().unwrap(), "2".parse().unwrap(), "a".parse().unwrap()]
+    };
+    let mut input = Cursor::new("id,a,b,c\nx,3,4,5\ny,5,10,25\n");
+    let rows: Vec<Row<u32>> = read_rows(&mut input, 3, &mut format).unwrap();
+    let rows: Vec<(usize, Polygon<u32>)> = rows.into_iter().map(|(n, r)| (n, Polygon(r).unsigned().unwrap())).collect();
+    assert_eq!(rows, vec![(2, Polygon(vec![5, 3, 3])), (3, Polygon(vec![25, 5, 5]))]);
+
+    let mut input = Cursor::new("id,a,b,c\n\nx,3,4,5\n  \n");
+    let rows = read_rows::<u32>(&mut input, 3, &mut format).unwrap();
+    assert_eq!(rows.iter().map(|(n, _)| *n).collect::<Vec<usize>>(), vec![3]);
+
+    let mut input = Cursor::new("id,a,b\nx,3,4\n");
+    assert!(read_rows::<u32>(&mut input, 3, &mut format).is_err());
+    assert!("0".parse::<Column>().is_err());
+}
+
+/// The sides read from one input line, with its line number.
+type Row<T> = (usize, Vec<Length<T>>);
+
+/// Read rows of `n` sides each with their line numbers, skipping blank
+/// lines.  When the format has a header row it is consumed here and used to
+/// resolve the named columns.
+fn read_rows<T: Side>(input: &mut dyn BufRead, n: usize, format: &mut Format) -> Result<Vec<Row<T>>, String> {
+    let mut lines = input.lines();
+    if format.header {
+        let header = lines.next()
+                          .unwrap_or(Ok(String::new()))
+                          .map_err(|e| e.to_string())?;
+        format.resolve(&header)?;
+    }
+    let offset = if format.header { 2 } else { 1 };
+
+    lines.enumerate()
+         .filter(|(_, r)| r.as_ref().map_or(true, |l| !l.trim().is_empty()))
+         .map(|(i, r)| {
+             let l = r.map_err(|e| e.to_string())?;
+             format.fields(&l)
+                   .and_then(|fields| parse_fields(&fields, n))
+                   .map(|row| (i + offset, row))
+                   .map_err(|e| format!("line {}: {}", i + offset, e))
+         })
+         .collect()
+}
+
+struct Options {
+    n: usize,
+    height: usize,
+    layout: Layout,
+    report: bool,
+    format: Format
+}
+
+fn solve<T: Side>(mut opts: Options) {
     let stdin = std::io::stdin();
-    let count = stdin.lock()
-                     .lines()
-                     .map(|r| r.expect("expected line of data"))
-                     .map(|l| parse_line(&l).expect("expected three integers"))
-                     .tuples()
-                     .flat_map(|((a0, b0, c0),
-                                 (a1, b1, c1),
-                                 (a2, b2, c2))|
-                               vec!(Triangle(a0, a1, a2),
-                                    Triangle(b0, b1, b2),
-                                    Triangle(c0, c1, c2)).into_iter())
-                     .filter(|t| is_triangle(*t))
-                     .count();
-    println!("{}", count);
+    let rows = read_rows::<T>(&mut stdin.lock(), opts.n, &mut opts.format);
+    let ps = match rows.and_then(|rows| read_polygons(&rows, opts.height, opts.layout)) {
+        Ok(ps) => ps,
+        Err(e) => return println!("error: {}", e)
+    };
+
+    if opts.report {
+        let mut r = Report::default();
+        for (lines, p) in ps {
+            r.add(lines, p)
+        }
+        print!("{}", r);
+    } else {
+        println!("{}", ps.iter().filter(|(_, p)| p.unsigned().is_some_and(|p| is_polygon(&p))).count());
+    }
 }
 
 fn main() {
-    let flag = std::env::args().nth(1).unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = args.first().expect("expected '-1' or '-2'");
+    let value = |name: &str| args.iter()
+                                 .position(|a| a == name)
+                                 .map(|i| args.get(i + 1).map(|v| v.as_str()).unwrap_or(""));
+    let option = |name: &str, default: usize| match value(name) {
+        Some(v) => v.parse().unwrap_or_else(|_| panic!("expected a number after '{}'", name)),
+        None => default
+    };
+    let has_flag = |name: &str| args.iter().any(|a| a == name);
+
+    let mut format = Format::whitespace();
+    if has_flag("--csv") {
+        format.delim = Delimiter::Char(',')
+    } else if has_flag("--tsv") {
+        format.delim = Delimiter::Char('\t')
+    } else if has_flag("--mixed") {
+        format.delim = Delimiter::Mixed
+    }
+    format.header = has_flag("--header");
+    if let Some(cols) = value("--columns") {
+        format.columns = cols.split(',').map(|c| c.parse().expect("expected column")).collect()
+    }
+
+    let sides = if format.columns.is_empty() { 3 } else { format.columns.len() };
+    let n = option("-n", sides);
+    let layout = match flag.as_ref() {
+        "-1" => Layout::Rows,
+        "-2" if has_flag("--diagonal") => Layout::Diagonals,
+        "-2" => Layout::Columns,
+        _ => return println!("expected '-1' or '-2'")
+    };
+    let opts = Options {
+        n,
+        height: option("-g", n),
+        layout,
+        report: has_flag("--report"),
+        format
+    };
 
-    match flag.as_ref() {
-        "-1" => part_one(),
-        "-2" => part_two(),
-        _ => println!("expected '-1' or '-2'")
+    match value("-w").unwrap_or("32") {
+        "32" => solve::<u32>(opts),
+        "64" => solve::<u64>(opts),
+        "128" => solve::<u128>(opts),
+        "big" => solve::<BigUint>(opts),
+        _ => println!("expected '-w' to be one of 32, 64, 128 or big")
     }
 }
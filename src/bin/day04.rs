@@ -1,41 +1,96 @@
 
 extern crate itertools;
+extern crate regex;
 
 use std::char;
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::io::BufRead;
-use std::str::{Chars, FromStr};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::str::FromStr;
 
 use itertools::Itertools;
+use regex::Regex;
 
 #[derive(Debug)]
 struct Counter {
-    counts: HashMap<char, u32>
+    counts: HashMap<char, u32>,
+    /// Characters in the order they were first seen.
+    order: Vec<char>
 }
 
 impl Counter {
     fn new() -> Counter {
-        Counter { counts: HashMap::new() }
+        Counter { counts: HashMap::new(), order: Vec::new() }
     }
 
     fn add_char(&mut self, ch: char) {
         let c = self.counts.entry(ch).or_insert(0);
+        if *c == 0 {
+            self.order.push(ch)
+        }
         *c += 1;
     }
 
+    #[cfg(test)]
     fn key(&self) -> String {
-        let mut entries: Vec<(char, u32)> = self.counts.iter().map(|(ch, c)| (*ch, *c)).collect();
-        // hack because ordering_chaining not in stable
+        self.key_with(&Policy::default())
+    }
+
+    fn key_with(&self, policy: &Policy) -> String {
+        let mut entries: Vec<(char, u32)> = self.counts
+                                                .iter()
+                                                .filter(|&(ch, _)| policy.digits || !ch.is_ascii_digit())
+                                                .map(|(ch, c)| (*ch, *c))
+                                                .collect();
+        let first = |ch: char| self.order.iter().position(|&c| c == ch);
         entries.sort_by(|&(cha, a), &(chb, b)| {
-                            let ord = b.cmp(&a);
-                            if ord == Ordering::Equal {
-                                cha.cmp(&chb)
-                            } else {
-                                ord
-                            }
+                            b.cmp(&a).then_with(|| match policy.tie_break {
+                                TieBreak::Alphabetical => cha.cmp(&chb),
+                                TieBreak::Reverse => chb.cmp(&cha),
+                                TieBreak::FirstAppearance => first(cha).cmp(&first(chb))
+                            })
                         });
-        entries.iter().take(5).map(|&(ch, _)| ch).collect()
+        entries.iter().take(policy.length).map(|&(ch, _)| ch).collect()
+    }
+}
+
+/// How letters with the same count are ordered in a checksum.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TieBreak {
+    Alphabetical,
+    Reverse,
+    FirstAppearance
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TieBreak, String> {
+        match s {
+            "alpha" => Ok(TieBreak::Alphabetical),
+            "reverse" => Ok(TieBreak::Reverse),
+            "first" => Ok(TieBreak::FirstAppearance),
+            _ => Err(format!("expected 'alpha', 'reverse' or 'first', got {:?}", s))
+        }
+    }
+}
+
+/// How a room's checksum is computed and written.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Policy {
+    length: usize,
+    tie_break: TieBreak,
+    /// Whether digits in the name are counted.
+    digits: bool,
+    open: char,
+    close: char
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy { length: 5, tie_break: TieBreak::Alphabetical, digits: false, open: '[', close: ']' }
     }
 }
 
@@ -43,7 +98,8 @@ impl Counter {
 struct RoomCode {
     name: Vec<String>,
     sector: u32,
-    key: String
+    key: String,
+    policy: Policy
 }
 
 impl RoomCode {
@@ -52,50 +108,624 @@ impl RoomCode {
         for c in self.name.iter().flat_map(|s| s.chars()) {
             counter.add_char(c)
         }
-        counter.key()
+        counter.key_with(&self.policy)
     }
 
     fn valid_code(&self) -> bool {
         self.compute_key() == self.key
     }
 
+    /// Compare the given checksum with the computed one, or `None` if they
+    /// match.
+    fn key_diff(&self) -> Option<KeyDiff> {
+        let expected = self.compute_key();
+        if expected == self.key {
+            return None
+        }
+
+        let missing = expected.chars().filter(|&c| !self.key.contains(c)).collect();
+        let extra = self.key.chars().filter(|&c| !expected.contains(c)).collect();
+        let mismatched = expected.chars()
+                                 .zip(self.key.chars())
+                                 .enumerate()
+                                 .filter(|&(_, (e, g))| e != g)
+                                 .map(|(i, _)| i + 1)
+                                 .collect();
+        Some(KeyDiff { expected, given: self.key.clone(), missing, extra, mismatched })
+    }
+
+    /// A copy of the room with the checksum computed from its name.
+    fn repair(&self) -> RoomCode {
+        RoomCode { name: self.name.clone(), sector: self.sector, key: self.compute_key(), policy: self.policy }
+    }
+
+    #[cfg(test)]
     fn decrypt(&self) -> String {
-        self.name
-            .iter()
-            .map(|name| name.chars().decrypt(self.sector % 26))
-            .intersperse(" ".to_string())
-            .collect()
+        self.decrypt_with(&Scheme::caesar()).expect("room name is not lowercase")
+    }
+
+    fn decrypt_with(&self, scheme: &Scheme) -> Result<String, String> {
+        scheme.decrypt(&self.name, self.sector).map(|words| words.join(" "))
+    }
+
+    /// Encrypt a plaintext name of space separated words into a room code
+    /// with a correct checksum.
+    fn encrypt(plain: &str, sector: u32, scheme: &Scheme, policy: &Policy) -> Result<RoomCode, String> {
+        let words: Vec<String> = plain.split_whitespace().map(|w| w.to_string()).collect();
+        if words.is_empty() {
+            return Err("empty name".to_string())
+        }
+
+        let mut rc = RoomCode { name: scheme.encrypt(&words, sector)?, sector, key: String::new(), policy: *policy };
+        rc.key = rc.compute_key();
+        if rc.key.is_empty() {
+            return Err(format!("no checksum can be computed for {:?}", rc.name.join("-")))
+        }
+        // the checksum can only hold some characters, so make sure the code
+        // reads back
+        RoomCode::parse(&rc.to_string(), policy).map_err(|e| format!("{} can't be read back: {}", rc, e))?;
+        Ok(rc)
+    }
+
+    /// A copy of the room with a checksum that is deliberately wrong, or an
+    /// error if the checksum computed from the name is empty.
+    fn decoy(&self) -> Result<RoomCode, String> {
+        let mut rc = self.repair();
+        let mut key: Vec<char> = rc.key.chars().collect();
+
+        match key.len() {
+            0 => return Err(format!("no checksum to get wrong for {:?}", self.name.join("-"))),
+            1 => {
+                // swap in the next character a checksum is allowed to hold
+                let digits = if self.policy.digits { "0123456789" } else { "" };
+                let allowed: Vec<char> = ('a'..='z').chain(digits.chars()).collect();
+                let next = allowed.iter().position(|&c| c == key[0]).map_or(0, |i| (i + 1) % allowed.len());
+                key[0] = allowed[next]
+            }
+            // the letters of a checksum are distinct so any rotation is wrong
+            _ => key.rotate_left(1)
+        }
+        rc.key = key.into_iter().collect();
+        Ok(rc)
     }
 }
 
-trait Decrypt {
-    fn decrypt(self, offset: u32) -> String;
+/// How a given checksum differs from the expected one.
+#[derive(Debug, PartialEq)]
+struct KeyDiff {
+    expected: String,
+    given: String,
+    /// Expected letters that are not in the given checksum.
+    missing: String,
+    /// Given letters that are not in the expected checksum.
+    extra: String,
+    /// 1-based positions where the two checksums differ.
+    mismatched: Vec<usize>
+}
+
+impl KeyDiff {
+    /// A single wrong letter or a single swap of neighbouring letters looks
+    /// like a typo rather than a decoy.
+    fn is_typo(&self) -> bool {
+        if self.expected.len() != self.given.len() {
+            return false
+        }
+        match self.mismatched.as_slice() {
+            [_] => true,
+            &[i, j] => {
+                let e = self.expected.as_bytes();
+                let g = self.given.as_bytes();
+                j == i + 1 && e[i - 1] == g[j - 1] && e[j - 1] == g[i - 1]
+            }
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for KeyDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let positions: Vec<String> = self.mismatched.iter().map(|i| i.to_string()).collect();
+        write!(f, "expected [{}] given [{}] missing {:?} extra {:?} differs at {}",
+               self.expected, self.given, self.missing, self.extra, positions.join(","))?;
+        if self.is_typo() {
+            write!(f, " (likely typo)")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RoomCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}{}{}{}", self.name.join("-"), self.sector, self.policy.open, self.key, self.policy.close)
+    }
+}
+
+/// A range of byte offsets into a parsed line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Span(usize, usize);
+
+#[derive(Debug, PartialEq)]
+enum ParseError {
+    /// There is nothing before the sector.
+    EmptyName(Span),
+    /// Two dashes in a row, or a dash at the start of the line.
+    EmptyWord(Span),
+    MissingSector(Span),
+    /// The sector does not fit in a `u32`.
+    SectorOverflow(Span),
+    MissingOpen(Span, char),
+    MissingClose(Span, char),
+    EmptyKey(Span),
+    /// A checksum character that is not a lowercase letter, or a digit when
+    /// the policy counts digits.
+    BadKeyChar(Span, char),
+    TrailingInput(Span)
+}
+
+impl ParseError {
+    fn span(&self) -> Span {
+        match *self {
+            ParseError::EmptyName(span) |
+            ParseError::EmptyWord(span) |
+            ParseError::MissingSector(span) |
+            ParseError::SectorOverflow(span) |
+            ParseError::MissingOpen(span, _) |
+            ParseError::MissingClose(span, _) |
+            ParseError::EmptyKey(span) |
+            ParseError::BadKeyChar(span, _) |
+            ParseError::TrailingInput(span) => span
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::EmptyName(_) => write!(f, "empty name")?,
+            ParseError::EmptyWord(_) => write!(f, "empty word in name")?,
+            ParseError::MissingSector(_) => write!(f, "missing sector")?,
+            ParseError::SectorOverflow(_) => write!(f, "sector is too large")?,
+            ParseError::MissingOpen(_, c) => write!(f, "expected '{}'", c)?,
+            ParseError::MissingClose(_, c) => write!(f, "expected '{}'", c)?,
+            ParseError::EmptyKey(_) => write!(f, "empty checksum")?,
+            ParseError::BadKeyChar(_, c) => write!(f, "unexpected {:?} in checksum", c)?,
+            ParseError::TrailingInput(_) => write!(f, "unexpected input after checksum")?
+        }
+        let Span(start, end) = self.span();
+        write!(f, " at bytes {}..{}", start, end)
+    }
+}
+
+/// Recursive descent parser for the grammar
+///
+/// ```text
+/// room   = word { "-" word } "-" sector open key close
+/// word   = one or more characters other than "-"
+/// sector = one or more digits
+/// key    = one or more lowercase letters (or digits, if counted)
+/// ```
+///
+/// where the last dash separated segment is always the sector and checksum.
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    policy: &'a Policy
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> Span {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !f(c) {
+                break
+            }
+            self.pos += c.len_utf8();
+        }
+        Span(start, self.pos)
+    }
+
+    fn expect(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn room(&mut self) -> Result<RoomCode, ParseError> {
+        let name = self.name()?;
+        let sector = self.sector()?;
+        let key = self.key()?;
+
+        if self.pos < self.s.len() {
+            return Err(ParseError::TrailingInput(Span(self.pos, self.s.len())))
+        }
+        Ok(RoomCode { name, sector, key, policy: *self.policy })
+    }
+
+    fn name(&mut self) -> Result<Vec<String>, ParseError> {
+        let head = &self.s[..self.s.find(self.policy.open).unwrap_or(self.s.len())];
+        let sector_start = head.rfind('-').map(|i| i + 1).unwrap_or(0);
+        if sector_start == 0 {
+            return Err(ParseError::EmptyName(Span(0, 0)))
+        }
+
+        let mut words = Vec::new();
+        while self.pos < sector_start {
+            let Span(start, end) = self.take_while(|c| c != '-');
+            if start == end {
+                return Err(ParseError::EmptyWord(Span(start, start + 1)))
+            }
+            words.push(self.s[start..end].to_string());
+            self.expect('-');
+        }
+        Ok(words)
+    }
+
+    fn sector(&mut self) -> Result<u32, ParseError> {
+        let Span(start, end) = self.take_while(|c| c.is_ascii_digit());
+        if start == end {
+            return Err(ParseError::MissingSector(Span(start, start)))
+        }
+        self.s[start..end].parse().map_err(|_| ParseError::SectorOverflow(Span(start, end)))
+    }
+
+    fn key(&mut self) -> Result<String, ParseError> {
+        let (open, close) = (self.policy.open, self.policy.close);
+        if !self.expect(open) {
+            return Err(ParseError::MissingOpen(Span(self.pos, self.pos), open))
+        }
+
+        let digits = self.policy.digits;
+        let Span(start, end) = self.take_while(|c| c != close && (c.is_ascii_lowercase() || (digits && c.is_ascii_digit())));
+        match self.peek() {
+            Some(c) if c == close => (),
+            Some(c) => return Err(ParseError::BadKeyChar(Span(end, end + c.len_utf8()), c)),
+            None => return Err(ParseError::MissingClose(Span(end, end), close))
+        }
+        if start == end {
+            return Err(ParseError::EmptyKey(Span(start, end)))
+        }
+        self.expect(close);
+        Ok(self.s[start..end].to_string())
+    }
 }
 
-impl<'a> Decrypt for Chars<'a> {
-    fn decrypt(self, offset: u32) -> String {
-        let a = 'a' as u32;
-        self.map(|c| char::from_u32((((c as u32) - a + offset) % 26) + a).unwrap())
-            .collect()
+impl RoomCode {
+    /// Parse a room code whose checksum follows `policy`.
+    fn parse(s: &str, policy: &Policy) -> Result<RoomCode, ParseError> {
+        Parser { s, pos: 0, policy }.room()
     }
 }
 
 impl FromStr for RoomCode {
-    type Err = String;
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<RoomCode, ParseError> {
+        RoomCode::parse(s, &Policy::default())
+    }
+}
+
+/// The characters room names are written in.  Ciphers work on positions in
+/// the alphabet rather than on the characters themselves.  Dashes and
+/// whitespace separate words, so they can't be letters.
+#[derive(Clone, Debug)]
+struct Alphabet(Vec<char>);
+
+impl Alphabet {
+    fn new(chars: &str) -> Result<Alphabet, String> {
+        let chars: Vec<char> = chars.chars().collect();
+        if chars.is_empty() {
+            return Err("empty alphabet".to_string())
+        }
+        if chars.iter().unique().count() != chars.len() {
+            return Err("alphabet has repeated characters".to_string())
+        }
+        if let Some(c) = chars.iter().find(|&&c| c == '-' || c.is_whitespace()) {
+            return Err(format!("alphabet can't hold the separator {:?}", c))
+        }
+        Ok(Alphabet(chars))
+    }
+
+    /// Check that rooms written in the alphabet can be read back under
+    /// `policy`, which takes its brackets as the start and end of the
+    /// checksum.
+    fn check_policy(&self, policy: &Policy) -> Result<(), String> {
+        match self.0.iter().find(|&&c| c == policy.open || c == policy.close) {
+            Some(c) => Err(format!("alphabet can't hold the checksum bracket {:?}", c)),
+            None => Ok(())
+        }
+    }
+
+    fn lowercase() -> Alphabet {
+        Alphabet(('a'..='z').collect())
+    }
+
+    fn len(&self) -> u32 {
+        self.0.len() as u32
+    }
 
+    fn index(&self, ch: char) -> Option<u32> {
+        self.0.iter().position(|&c| c == ch).map(|i| i as u32)
+    }
+}
+
+/// A substitution cipher over the positions of an alphabet of `n` letters.
+/// `index` counts the letters of the whole room name, so ciphers with a key
+/// stream carry on across words.
+trait Cipher {
+    fn encrypt(&self, x: u32, n: u32, sector: u32, index: usize) -> u32;
+    fn decrypt(&self, y: u32, n: u32, sector: u32, index: usize) -> u32;
+
+    /// Check that the cipher can be used with an alphabet of `n` letters.
     #[allow(unused_variables)]
-    fn from_str(s: &str) -> Result<RoomCode, String> {
-        let mut segments: Vec<String> = s.split('-').map(|s| s.to_string()).collect();
-        let sector_key = try!(segments.pop().ok_or("empty string"));
-        let j = try!(sector_key.find('[').ok_or("missing '['"));
-        let k = try!(sector_key.find(']').ok_or("missing ']'"));
-        let sector: u32 = try!(sector_key[0..j].parse().map_err(|e| "not an integer sector code"));
-        let key = sector_key[j+1..k].to_string();
+    fn check(&self, n: u32) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Shift every letter back by the sector, so decrypting shifts it forward.
+struct Caesar;
+
+impl Cipher for Caesar {
+    fn encrypt(&self, x: u32, n: u32, sector: u32, _: usize) -> u32 {
+        (x + n - sector % n) % n
+    }
 
-        Ok(RoomCode { name: segments, sector: sector, key: key })
+    fn decrypt(&self, y: u32, n: u32, sector: u32, _: usize) -> u32 {
+        (y + sector % n) % n
     }
 }
 
+/// Shift every letter by a fixed amount whatever the sector.
+struct Rot(u32);
+
+impl Cipher for Rot {
+    fn encrypt(&self, x: u32, n: u32, _: u32, _: usize) -> u32 {
+        (x + self.0 % n) % n
+    }
+
+    fn decrypt(&self, y: u32, n: u32, _: u32, _: usize) -> u32 {
+        (y + n - self.0 % n) % n
+    }
+}
+
+/// Reverse the alphabet.
+struct Atbash;
+
+impl Cipher for Atbash {
+    fn encrypt(&self, x: u32, n: u32, _: u32, _: usize) -> u32 {
+        n - 1 - x
+    }
+
+    fn decrypt(&self, y: u32, n: u32, _: u32, _: usize) -> u32 {
+        n - 1 - y
+    }
+}
+
+/// Like `Caesar` but each letter is shifted by the next digit of the sector,
+/// cycling through them.
+struct Vigenere;
+
+impl Vigenere {
+    fn shift(sector: u32, index: usize) -> u32 {
+        let digits: Vec<u32> = sector.to_string().chars().map(|d| d.to_digit(10).unwrap()).collect();
+        digits[index % digits.len()]
+    }
+}
+
+impl Cipher for Vigenere {
+    fn encrypt(&self, x: u32, n: u32, sector: u32, index: usize) -> u32 {
+        (x + n - Vigenere::shift(sector, index) % n) % n
+    }
+
+    fn decrypt(&self, y: u32, n: u32, sector: u32, index: usize) -> u32 {
+        (y + Vigenere::shift(sector, index)) % n
+    }
+}
+
+/// Map each letter `x` to `a * x + b`.  `a` must be coprime with the size of
+/// the alphabet for the cipher to be reversible.
+struct Affine(u32, u32);
+
+impl Affine {
+    fn inverse(&self, n: u32) -> Option<u32> {
+        (0..n).find(|&i| (self.0 % n) * i % n == 1 % n)
+    }
+}
+
+impl Cipher for Affine {
+    fn encrypt(&self, x: u32, n: u32, _: u32, _: usize) -> u32 {
+        ((self.0 % n) * x + self.1 % n) % n
+    }
+
+    fn decrypt(&self, y: u32, n: u32, _: u32, _: usize) -> u32 {
+        let inv = self.inverse(n).expect("affine key is not invertible");
+        inv * ((y + n - self.1 % n) % n) % n
+    }
+
+    fn check(&self, n: u32) -> Result<(), String> {
+        self.inverse(n)
+            .map(|_| ())
+            .ok_or(format!("affine key {} is not coprime with alphabet size {}", self.0, n))
+    }
+}
+
+impl FromStr for Box<dyn Cipher> {
+    type Err = String;
+
+    /// Parse one of `caesar`, `rot<N>`, `atbash`, `vigenere` or `affine:<A>,<B>`.
+    fn from_str(s: &str) -> Result<Box<dyn Cipher>, String> {
+        let number = |n: &str| n.parse::<u32>().map_err(|_| format!("expected a number, got {:?}", n));
+
+        match s {
+            "caesar" => Ok(Box::new(Caesar)),
+            "atbash" => Ok(Box::new(Atbash)),
+            "vigenere" => Ok(Box::new(Vigenere)),
+            _ if s.starts_with("rot") => Ok(Box::new(Rot(number(&s[3..])?))),
+            _ if s.starts_with("affine:") => {
+                let (a, b) = s[7..].split_at(s[7..].find(',').ok_or("expected 'affine:<A>,<B>'")?);
+                Ok(Box::new(Affine(number(a)?, number(&b[1..])?)))
+            }
+            _ => Err(format!("unknown cipher {:?}", s))
+        }
+    }
+}
+
+/// How a set of rooms is encrypted.
+struct Scheme {
+    cipher: Box<dyn Cipher>,
+    alphabet: Alphabet
+}
+
+impl Scheme {
+    fn new(cipher: Box<dyn Cipher>, alphabet: Alphabet) -> Result<Scheme, String> {
+        cipher.check(alphabet.len())?;
+        Ok(Scheme { cipher, alphabet })
+    }
+
+    fn caesar() -> Scheme {
+        Scheme { cipher: Box::new(Caesar), alphabet: Alphabet::lowercase() }
+    }
+
+    /// Maps every letter through `f`.  Characters outside the alphabet are
+    /// an error, unless `keep` is set, in which case they're left as they
+    /// are and don't move a key stream on.
+    fn apply<F>(&self, words: &[String], keep: bool, f: F) -> Result<Vec<String>, String>
+        where F: Fn(u32, usize) -> u32
+    {
+        let mut index = 0;
+        words.iter()
+             .map(|word| word.chars()
+                             .map(|c| {
+                                 let x = match self.alphabet.index(c) {
+                                     Some(x) => x,
+                                     None if keep => return Ok(c),
+                                     None => return Err(format!("{:?} is not in the alphabet", c))
+                                 };
+                                 let y = f(x, index);
+                                 index += 1;
+                                 Ok(self.alphabet.0[y as usize])
+                             })
+                             .collect())
+             .collect()
+    }
+
+    fn encrypt(&self, words: &[String], sector: u32) -> Result<Vec<String>, String> {
+        self.apply(words, false, |x, i| self.cipher.encrypt(x, self.alphabet.len(), sector, i))
+    }
+
+    fn decrypt(&self, words: &[String], sector: u32) -> Result<Vec<String>, String> {
+        self.apply(words, false, |y, i| self.cipher.decrypt(y, self.alphabet.len(), sector, i))
+    }
+
+    /// Like `decrypt`, but characters outside the alphabet pass through.
+    fn decrypt_lenient(&self, words: &[String], sector: u32) -> Vec<String> {
+        self.apply(words, true, |y, i| self.cipher.decrypt(y, self.alphabet.len(), sector, i))
+            .expect("lenient decryption can't fail")
+    }
+}
+
+/// What to look for in a decrypted room name.
+enum Pattern {
+    Regex(Regex),
+    /// Matches names containing the pattern's letters in order, ignoring
+    /// spaces, so `"npole"` matches `"northpole object storage"`.
+    Fuzzy(String)
+}
+
+impl Pattern {
+    fn matches(&self, name: &str) -> bool {
+        match *self {
+            Pattern::Regex(ref re) => re.is_match(name),
+            Pattern::Fuzzy(ref pat) => {
+                let mut chars = name.chars();
+                pat.chars()
+                   .filter(|c| !c.is_whitespace())
+                   .all(|p| chars.any(|c| c == p))
+            }
+        }
+    }
+}
+
+/// The valid rooms whose names, decrypted with `scheme`, match `pattern`.
+fn find_rooms<'a>(rooms: &'a [RoomCode], scheme: &Scheme, pattern: &Pattern) -> Vec<&'a RoomCode> {
+    rooms.iter()
+         .filter(|rc| rc.valid_code())
+         .filter(|rc| rc.decrypt_with(scheme).is_ok_and(|name| pattern.matches(&name)))
+         .collect()
+}
+
+/// Relative frequencies of the letters `a` to `z` in English text.
+static ENGLISH_FREQS: [f64; 26] = [
+    0.08167, 0.01492, 0.02782, 0.04253, 0.12702, 0.02228, 0.02015, 0.06094, 0.06966,
+    0.00153, 0.00772, 0.04025, 0.02406, 0.06749, 0.07507, 0.01929, 0.00095, 0.05987,
+    0.06327, 0.09056, 0.02758, 0.00978, 0.02360, 0.00150, 0.01974, 0.00074
+];
+
+impl Counter {
+    /// Log-likelihood of the counted letters being drawn from English text.
+    fn english_log_likelihood(&self) -> f64 {
+        self.counts
+            .iter()
+            .filter(|&(ch, _)| ch.is_ascii_lowercase())
+            .map(|(&ch, &c)| c as f64 * ENGLISH_FREQS[(ch as u8 - b'a') as usize].ln())
+            .sum()
+    }
+}
+
+/// A guess at the shift used to encrypt a room name.
+#[derive(Debug)]
+struct Candidate {
+    shift: u32,
+    name: String,
+    score: f64,
+    confidence: f64
+}
+
+/// Guess the shift of an encrypted room name without knowing its sector.
+/// Every shift is scored by how likely its letters are in English text.  With
+/// a word list, the letters of recognized words count as certain.  Characters
+/// outside the lowercase alphabet are left alone and not scored.  The
+/// candidates are returned best first with confidences that sum to one.
+fn crack(name: &[String], words: Option<&HashSet<String>>) -> Vec<Candidate> {
+    let scheme = Scheme::caesar();
+    let mut candidates: Vec<Candidate> = (0..26).map(|shift| try_shift(&scheme, name, shift, words)).collect();
+
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    let best = candidates[0].score;
+    let total: f64 = candidates.iter().map(|c| (c.score - best).exp()).sum();
+    for c in candidates.iter_mut() {
+        c.confidence = (c.score - best).exp() / total
+    }
+    candidates
+}
+
+fn try_shift(scheme: &Scheme, name: &[String], shift: u32, words: Option<&HashSet<String>>) -> Candidate {
+    let plain = scheme.decrypt_lenient(name, shift);
+    let mut counter = Counter::new();
+
+    for word in plain.iter().filter(|w| !words.is_some_and(|ws| ws.contains(*w))) {
+        for c in word.chars() {
+            counter.add_char(c)
+        }
+    }
+    Candidate { shift, name: plain.join(" "), score: counter.english_log_likelihood(), confidence: 0.0 }
+}
+
+fn read_words(path: &str) -> Result<HashSet<String>, String> {
+    let f = File::open(path).map_err(|e| format!("could not open word list {}: {}", path, e))?;
+    BufReader::new(f).lines()
+                     .map(|l| l.map(|w| w.trim().to_lowercase())
+                               .map_err(|e| format!("could not read word list {}: {}", path, e)))
+                     .collect()
+}
+
 #[test]
 fn aoc04_test_counter_add_char() {
     let mut c = Counter::new();
@@ -144,6 +774,31 @@ fn aoc04_test_parse_room_code() {
     assert_eq!(room_code.name.len(), 5);
 }
 
+#[test]
+fn aoc04_test_parse_errors() {
+    let err = |s: &str| s.parse::<RoomCode>().unwrap_err();
+
+    assert_eq!(err("123[abc]"), ParseError::EmptyName(Span(0, 0)));
+    assert_eq!(err(""), ParseError::EmptyName(Span(0, 0)));
+    assert_eq!(err("-123[abc]"), ParseError::EmptyWord(Span(0, 1)));
+    assert_eq!(err("ab--cd-123[abc]"), ParseError::EmptyWord(Span(3, 4)));
+    assert_eq!(err("ab-cd-[abc]"), ParseError::MissingSector(Span(6, 6)));
+    assert_eq!(err("ab-cd-"), ParseError::MissingSector(Span(6, 6)));
+    assert_eq!(err("ab-99999999999[abc]"), ParseError::SectorOverflow(Span(3, 14)));
+    assert_eq!(err("ab-123abc]"), ParseError::MissingOpen(Span(6, 6), '['));
+    assert_eq!(err("ab-123]abc["), ParseError::MissingOpen(Span(6, 6), '['));
+    assert_eq!(err("ab-123[abc"), ParseError::MissingClose(Span(10, 10), ']'));
+    assert_eq!(err("ab-123[]"), ParseError::EmptyKey(Span(7, 7)));
+    assert_eq!(err("ab-123[aBc]"), ParseError::BadKeyChar(Span(8, 9), 'B'));
+    assert_eq!(err("ab-123[a1c]"), ParseError::BadKeyChar(Span(8, 9), '1'));
+    assert_eq!(err("ab-123[a-b]"), ParseError::BadKeyChar(Span(8, 9), '-'));
+    assert_eq!(err("ab-123[abc]x"), ParseError::TrailingInput(Span(11, 12)));
+
+    let policy = Policy { digits: true, ..Policy::default() };
+    assert_eq!(RoomCode::parse("ab1-123[a1b]", &policy).unwrap().key, "a1b");
+    assert_eq!(err("ab-123[a!c]").to_string(), "unexpected '!' in checksum at bytes 8..9");
+}
+
 #[test]
 fn aoc04_test_room_code_key() {
     let n = vec!("aaaaa".to_string(),
@@ -151,7 +806,7 @@ fn aoc04_test_room_code_key() {
                  "z".to_string(),
                  "y".to_string(),
                  "x".to_string());
-    let r = RoomCode { name: n, sector: 123, key: "abxyz".to_string() };
+    let r = RoomCode { name: n, sector: 123, key: "abxyz".to_string(), policy: Policy::default() };
     assert_eq!(r.key, r.compute_key());
 }
 
@@ -169,39 +824,412 @@ fn aoc04_test_decrypt() {
     assert_eq!(rc.decrypt(), "very encrypted name");
 }
 
-fn part_one() {
+#[test]
+fn aoc04_test_encrypt() {
+    let rc = RoomCode::encrypt("very encrypted name", 343, &Scheme::caesar(), &Policy::default()).unwrap();
+    assert_eq!(rc.name, vec!["qzmt", "zixmtkozy", "ivhz"]);
+    assert!(rc.valid_code());
+    assert_eq!(rc.decrypt(), "very encrypted name");
+
+    let rc: RoomCode = rc.to_string().parse().unwrap();
+    assert!(rc.valid_code());
+    assert_eq!(rc.decrypt(), "very encrypted name");
+
+    assert!(RoomCode::encrypt("Not Lowercase", 1, &Scheme::caesar(), &Policy::default()).is_err());
+    assert!(RoomCode::encrypt("  ", 1, &Scheme::caesar(), &Policy::default()).is_err());
+
+    // a checksum that comes out empty can't be read back
+    let digits = Scheme::new(Box::new(Caesar), Alphabet::new("0123456789").unwrap()).unwrap();
+    assert!(RoomCode::encrypt("123", 5, &digits, &Policy::default()).is_err());
+    let policy = Policy { length: 0, ..Policy::default() };
+    assert!(RoomCode::encrypt("abc", 5, &Scheme::caesar(), &policy).is_err());
+
+    let atbash = Scheme::new(Box::new(Atbash), Alphabet::lowercase()).unwrap();
+    let rc = RoomCode::encrypt("very encrypted name", 343, &atbash, &Policy::default()).unwrap();
+    assert_eq!(rc.name, vec!["evib", "vmxibkgvw", "mznv"]);
+    assert!(rc.valid_code());
+}
+
+#[test]
+fn aoc04_test_decoy() {
+    for plain in &["very encrypted name", "aaaa", "northpole object storage"] {
+        let rc = RoomCode::encrypt(plain, 991, &Scheme::caesar(), &Policy::default()).unwrap();
+        let rc: RoomCode = rc.decoy().unwrap().to_string().parse().unwrap();
+        assert!(!rc.valid_code());
+        assert_eq!(rc.decrypt(), *plain);
+    }
+
+    let rc = RoomCode { name: vec!["678".to_string()], sector: 5, key: "a".to_string(), policy: Policy::default() };
+    assert!(rc.decoy().is_err());
+    let policy = Policy { length: 0, ..Policy::default() };
+    let rc = RoomCode { name: vec!["abc".to_string()], sector: 5, key: "a".to_string(), policy };
+    assert!(rc.decoy().is_err());
+
+    let digits = Scheme::new(Box::new(Caesar), Alphabet::new("0123456789").unwrap()).unwrap();
+
+    let policy = Policy { digits: true, ..Policy::default() };
+    let rc = RoomCode::encrypt("444", 5, &digits, &policy).unwrap();
+    assert_eq!(rc.key, "9");
+    let decoy = rc.decoy().unwrap();
+    assert_eq!(decoy.key, "a");
+    assert!(!RoomCode::parse(&decoy.to_string(), &policy).unwrap().valid_code());
+}
+
+#[test]
+fn aoc04_test_key_diff() {
+    let rc: RoomCode = "aaaaa-bbb-z-y-x-123[abxyz]".parse().unwrap();
+    assert_eq!(rc.key_diff(), None);
+
+    let rc: RoomCode = "totally-real-room-200[decoy]".parse().unwrap();
+    let diff = rc.key_diff().unwrap();
+    assert_eq!(diff.expected, "loart");
+    assert_eq!(diff.missing, "lart");
+    assert_eq!(diff.extra, "decy");
+    assert_eq!(diff.mismatched, vec![1, 2, 3, 4, 5]);
+    assert!(!diff.is_typo());
+
+    let swapped: RoomCode = "aaaaa-bbb-z-y-x-123[baxyz]".parse().unwrap();
+    assert!(swapped.key_diff().unwrap().is_typo());
+    let wrong_letter: RoomCode = "aaaaa-bbb-z-y-x-123[abxyq]".parse().unwrap();
+    assert!(wrong_letter.key_diff().unwrap().is_typo());
+    let rotated: RoomCode = "aaaaa-bbb-z-y-x-123[bxyza]".parse().unwrap();
+    assert!(!rotated.key_diff().unwrap().is_typo());
+}
+
+#[test]
+fn aoc04_test_repair() {
+    let rc: RoomCode = "totally-real-room-200[decoy]".parse().unwrap();
+    let fixed = rc.repair();
+    assert!(fixed.valid_code());
+    assert_eq!(fixed.to_string(), "totally-real-room-200[loart]");
+}
+
+#[test]
+fn aoc04_test_find_rooms() {
+    let rooms: Vec<RoomCode> = ["kloqemlib-lygbzq-pqloxdb-991[lbqod]",
+                                "qzmt-zixmtkozy-ivhz-343[zimth]",
+                                "kloqemlib-lygbzq-pqloxdb-991[bqodl]"]
+                                   .iter()
+                                   .map(|s| s.parse().unwrap())
+                                   .collect();
+
+    let re = Pattern::Regex(Regex::new("north ?pole").unwrap());
+    let scheme = Scheme::caesar();
+    let found: Vec<u32> = find_rooms(&rooms, &scheme, &re).iter().map(|rc| rc.sector).collect();
+    assert_eq!(found, vec![991]);
+
+    let fuzzy = Pattern::Fuzzy("npole stor".to_string());
+    assert_eq!(find_rooms(&rooms, &scheme, &fuzzy).len(), 1);
+    let fuzzy = Pattern::Fuzzy("vry nam".to_string());
+    assert_eq!(find_rooms(&rooms, &scheme, &fuzzy)[0].sector, 343);
+    assert!(!Pattern::Fuzzy("elopnhtron".to_string()).matches("northpole"));
+}
+
+#[test]
+fn aoc04_test_ciphers() {
+    let words: Vec<String> = vec!["very".to_string(), "encrypted".to_string(), "name".to_string()];
+    let ciphers = ["caesar", "rot13", "atbash", "vigenere", "affine:5,8"];
+
+    for name in ciphers.iter() {
+        let scheme = Scheme::new(name.parse().unwrap(), Alphabet::lowercase()).unwrap();
+        let encrypted = scheme.encrypt(&words, 343).unwrap();
+        assert!(encrypted != words, "{} left the name unchanged", name);
+        assert_eq!(scheme.decrypt(&encrypted, 343).unwrap(), words);
+    }
+
+    let rc: RoomCode = "qzmt-zixmtkozy-ivhz-343[xxx]".parse().unwrap();
+    assert_eq!(rc.decrypt_with(&Scheme::caesar()).unwrap(), rc.decrypt());
+
+    let rot13 = Scheme::new("rot13".parse().unwrap(), Alphabet::lowercase()).unwrap();
+    assert_eq!(rot13.encrypt(&["abc".to_string()], 1).unwrap(), vec!["nop"]);
+    let atbash = Scheme::new("atbash".parse().unwrap(), Alphabet::lowercase()).unwrap();
+    assert_eq!(atbash.encrypt(&["abz".to_string()], 1).unwrap(), vec!["zya"]);
+    let vigenere = Scheme::new("vigenere".parse().unwrap(), Alphabet::lowercase()).unwrap();
+    assert_eq!(vigenere.decrypt(&["aa".to_string(), "aa".to_string()], 120).unwrap(), vec!["bc", "ab"]);
+}
+
+#[test]
+fn aoc04_test_alphabet() {
+    let digits = Alphabet::new("0123456789").unwrap();
+    let scheme = Scheme::new(Box::new(Caesar), digits.clone()).unwrap();
+    assert_eq!(scheme.decrypt(&["0199".to_string()], 13).unwrap(), vec!["3422"]);
+    assert!(scheme.decrypt(&["abc".to_string()], 13).is_err());
+
+    assert!(Scheme::new("affine:5,1".parse().unwrap(), digits).is_err());
+    assert!(Alphabet::new("abca").is_err());
+    assert!(Alphabet::new("").is_err());
+    assert!(Alphabet::new("abc-").is_err());
+    assert!(Alphabet::new("ab c").is_err());
+
+    let brackets = Alphabet::new("abc()").unwrap();
+    assert!(brackets.check_policy(&Policy::default()).is_ok());
+    assert!(brackets.check_policy(&Policy { open: '(', close: ')', ..Policy::default() }).is_err());
+    assert!(Alphabet::new("abc]").unwrap().check_policy(&Policy::default()).is_err());
+    let scheme = Scheme::new(Box::new(Caesar), brackets).unwrap();
+    assert!(RoomCode::encrypt("abc", 1, &scheme, &Policy::default()).is_err());
+    assert!("rotx".parse::<Box<dyn Cipher>>().is_err());
+    assert!("enigma".parse::<Box<dyn Cipher>>().is_err());
+}
+
+#[test]
+fn aoc04_test_checksum_policy() {
+    let mut c = Counter::new();
+    for ch in "bbaazyy1111c".chars() {
+        c.add_char(ch)
+    }
+    let mut policy = Policy::default();
+    assert_eq!(c.key_with(&policy), "abycz");
+
+    policy.tie_break = TieBreak::Reverse;
+    assert_eq!(c.key_with(&policy), "ybazc");
+    policy.tie_break = TieBreak::FirstAppearance;
+    assert_eq!(c.key_with(&policy), "bayzc");
+
+    policy.digits = true;
+    policy.length = 3;
+    assert_eq!(c.key_with(&policy), "1ba");
+}
+
+#[test]
+fn aoc04_test_checksum_brackets() {
+    let policy = Policy { open: '(', close: ')', length: 3, ..Policy::default() };
+    let rc = RoomCode::parse("aaaaa-bbb-z-y-x-123(abx)", &policy).unwrap();
+    assert_eq!(rc.key, "abx");
+    assert!(rc.valid_code());
+    assert_eq!(rc.to_string(), "aaaaa-bbb-z-y-x-123(abx)");
+    assert!(RoomCode::parse("aaaaa-bbb-z-y-x-123[abx]", &policy).is_err());
+}
+
+#[test]
+fn aoc04_test_crack() {
+    let rc: RoomCode = "qzmt-zixmtkozy-ivhz-343[xxx]".parse().unwrap();
+    let candidates = crack(&rc.name, None);
+    assert_eq!(candidates.len(), 26);
+    assert_eq!(candidates[0].shift, rc.sector % 26);
+    assert_eq!(candidates[0].name, "very encrypted name");
+    assert!(candidates[0].confidence > 0.5);
+    assert!(candidates.windows(2).all(|w| w[0].score >= w[1].score));
+
+    let total: f64 = candidates.iter().map(|c| c.confidence).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+}
+
+#[test]
+fn aoc04_test_crack_non_letters() {
+    let rc: RoomCode = "qzmt-zixmtkozy1-ivhz-343[xxx]".parse().unwrap();
+    let candidates = crack(&rc.name, None);
+    assert_eq!(candidates[0].name, "very encrypted1 name");
+    assert!(rc.decrypt_with(&Scheme::caesar()).is_err());
+
+    let rc: RoomCode = "abcde1-1[abcde]".parse().unwrap();
+    assert!(rc.valid_code());
+    assert!(crack(&rc.name, None).iter().all(|c| c.name.ends_with('1')));
+}
+
+#[test]
+fn aoc04_test_crack_with_words() {
+    let words: HashSet<String> = vec!["very", "encrypted", "name"].into_iter().map(|w| w.to_string()).collect();
+    let rc: RoomCode = "qzmt-zixmtkozy-ivhz-343[xxx]".parse().unwrap();
+    let candidates = crack(&rc.name, Some(&words));
+    assert_eq!(candidates[0].name, "very encrypted name");
+    assert!(candidates[0].confidence > 0.999);
+}
+
+/// Each line on stdin with its line number and what it parses to.
+fn parse_lines(policy: &Policy) -> Vec<(usize, String, Result<RoomCode, ParseError>)> {
     let stdin = std::io::stdin();
+    stdin.lock()
+         .lines()
+         .enumerate()
+         .map(|(i, line)| {
+             let line = line.unwrap();
+             let rc = RoomCode::parse(&line, policy);
+             (i + 1, line, rc)
+         })
+         .collect()
+}
+
+/// Read the rooms on stdin with their line numbers, skipping and reporting
+/// any lines that don't parse.
+fn read_rooms(policy: &Policy) -> Vec<(usize, RoomCode)> {
+    parse_lines(policy).into_iter()
+                       .filter_map(|(n, line, rc)| match rc {
+                           Ok(rc) => Some((n, rc)),
+                           Err(e) => {
+                               eprintln!("skipping line {}: {}: {:?}", n, e, line);
+                               None
+                           }
+                       })
+                       .collect()
+}
+
+fn part_one(policy: &Policy) {
     let x : u32 =
-        stdin.lock()
-             .lines()
-             .map(|s| s.unwrap().parse::<RoomCode>().unwrap())
-             .filter(|rc| rc.valid_code())
-             .map(|rc| rc.sector)
-             .sum();
+        read_rooms(policy).iter()
+                          .filter(|&(_, rc)| rc.valid_code())
+                          .map(|(_, rc)| rc.sector)
+                          .sum();
 
      println!("{:?}", x);
 }
 
-fn part_two() {
+fn part_two(scheme: &Scheme, policy: &Policy, pattern: Option<Pattern>) {
+    let rooms: Vec<RoomCode> = read_rooms(policy).into_iter().map(|(_, rc)| rc).collect();
+
+    match pattern {
+        Some(pattern) => {
+            for rc in find_rooms(&rooms, scheme, &pattern) {
+                println!("{}", rc.sector);
+            }
+        }
+        None => {
+            for rc in rooms.iter().filter(|rc| rc.valid_code()) {
+                match rc.decrypt_with(scheme) {
+                    Ok(name) => println!("{:<4} {}", rc.sector, name),
+                    Err(e) => println!("{:<4} error: {}", rc.sector, e)
+                }
+            }
+        }
+    }
+}
+
+fn crack_rooms(policy: &Policy, words: Option<&HashSet<String>>) {
+    for (_, rc) in read_rooms(policy).iter().filter(|&(_, rc)| rc.valid_code()) {
+        let best = &crack(&rc.name, words)[0];
+        let mark = match rc.decrypt_with(&Scheme::caesar()) {
+            Ok(ref name) if *name == best.name => "",
+            Ok(_) => " (wrong)",
+            Err(_) => " (can't check)"
+        };
+        println!("{:<4} {:>2} {:.3} {}{}", rc.sector, best.shift, best.confidence, best.name, mark);
+    }
+}
+
+/// Report how each invalid room's checksum differs from the expected one,
+/// labelled with its line number.  With `repair` the input is echoed
+/// instead, with every checksum corrected and lines that don't parse left
+/// as they are.
+fn check_rooms(policy: &Policy, repair: bool) {
+    for (n, line, rc) in parse_lines(policy) {
+        match rc {
+            Ok(rc) => if repair {
+                println!("{}", rc.repair())
+            } else if let Some(diff) = rc.key_diff() {
+                println!("{:<4} {}: {}", n, rc, diff)
+            },
+            Err(e) => {
+                eprintln!("skipping line {}: {}: {:?}", n, e, line);
+                if repair {
+                    println!("{}", line)
+                }
+            }
+        }
+    }
+}
+
+/// Encrypt lines of the form `<sector> <plaintext name>` into room codes.
+fn encrypt_rooms(scheme: &Scheme, policy: &Policy, decoy: bool) {
     let stdin = std::io::stdin();
-    let x =
-        stdin.lock()
-             .lines()
-             .map(|s| s.unwrap().parse::<RoomCode>().unwrap())
-             .filter(|rc| rc.valid_code())
-             .map(|rc| (rc.decrypt(), rc.sector));
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        let mut parts = line.trim().splitn(2, ' ');
+        let sector = parts.next().unwrap_or("");
+        let plain = parts.next().unwrap_or("");
+        let rc = sector.parse()
+                       .map_err(|_| format!("expected a sector, got {:?}", sector))
+                       .and_then(|sector| RoomCode::encrypt(plain, sector, scheme, policy))
+                       .and_then(|rc| if decoy { rc.decoy() } else { Ok(rc) });
+
+        match rc {
+            Ok(rc) => println!("{}", rc),
+            Err(e) => println!("error: {}", e)
+        }
+    }
+}
+
+/// The pattern given with `--find`, matched fuzzily when `--fuzzy` is also
+/// given.
+fn find_pattern(args: &[String]) -> Option<Pattern> {
+    let i = args.iter().position(|a| a == "--find")?;
+    let p = args.get(i + 1).expect("expected a pattern after '--find'");
+
+    if args.iter().any(|a| a == "--fuzzy") {
+        Some(Pattern::Fuzzy(p.to_string()))
+    } else {
+        Some(Pattern::Regex(Regex::new(p).expect("invalid regex")))
+    }
+}
+
+/// The scheme picked with `--cipher` and `--alphabet`, a Caesar shift over
+/// lowercase letters by default.
+fn scheme(args: &[String]) -> Result<Scheme, String> {
+    let value = |name: &str| args.iter()
+                                 .position(|a| a == name)
+                                 .and_then(|i| args.get(i + 1));
+    let cipher: Box<dyn Cipher> = match value("--cipher") {
+        Some(c) => c.parse()?,
+        None => Box::new(Caesar)
+    };
+    let alphabet = match value("--alphabet") {
+        Some(a) => Alphabet::new(a)?,
+        None => Alphabet::lowercase()
+    };
+    Scheme::new(cipher, alphabet)
+}
+
+/// The checksum policy picked with `--checksum-length`, `--tie-break`,
+/// `--digits` and `--brackets`.
+fn policy(args: &[String]) -> Result<Policy, String> {
+    let value = |name: &str| args.iter()
+                                 .position(|a| a == name)
+                                 .and_then(|i| args.get(i + 1));
+    let mut policy = Policy::default();
 
-    for (name, sector) in x {
-        println!("{:<4} {}", sector, name);
+    if let Some(n) = value("--checksum-length") {
+        policy.length = n.parse().map_err(|_| format!("expected a checksum length, got {:?}", n))?
     }
+    if let Some(t) = value("--tie-break") {
+        policy.tie_break = t.parse()?
+    }
+    if let Some(b) = value("--brackets") {
+        let b: Vec<char> = b.chars().collect();
+        if b.len() != 2 {
+            return Err("expected '--brackets' to be two characters".to_string())
+        }
+        policy.open = b[0];
+        policy.close = b[1];
+    }
+    policy.digits = args.iter().any(|a| a == "--digits");
+    Ok(policy)
 }
 
 fn main () {
-    let flag = std::env::args().nth(1).unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = args.first().expect("expected '-1', '-2', '-3', '-e' or '-c'");
+    let has_flag = |name: &str| args.iter().any(|a| a == name);
+    let (scheme, policy) = match scheme(&args).and_then(|s| policy(&args).map(|p| (s, p)))
+                                              .and_then(|(s, p)| s.alphabet.check_policy(&p).map(|_| (s, p))) {
+        Ok(sp) => sp,
+        Err(e) => return println!("error: {}", e)
+    };
 
     match flag.as_ref() {
-        "-1" => part_one(),
-        "-2" => part_two(),
-        _ => println!("expected '-1' or '-2'")
+        "-1" => part_one(&policy),
+        "-2" => part_two(&scheme, &policy, find_pattern(&args)),
+        "-3" => {
+            let words = args.iter()
+                            .position(|a| a == "--words")
+                            .map(|i| args.get(i + 1)
+                                         .ok_or("expected a word list after '--words'".to_string())
+                                         .and_then(|path| read_words(path)))
+                            .transpose();
+            match words {
+                Ok(words) => crack_rooms(&policy, words.as_ref()),
+                Err(e) => println!("error: {}", e)
+            }
+        }
+        "-e" => encrypt_rooms(&scheme, &policy, has_flag("--decoy")),
+        "-c" => check_rooms(&policy, has_flag("--repair")),
+        _ => println!("expected '-1', '-2', '-3', '-e' or '-c'")
     }
 }
@@ -1,9 +1,246 @@
 
+extern crate ctrlc;
 extern crate md5;
+extern crate sha1;
+extern crate sha2;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A hash of up to 32 bytes, kept on the stack so checking a try doesn't
+/// allocate.
+#[derive(Clone, Copy)]
+struct Digest {
+    bytes: [u8; 32],
+    len: usize
+}
+
+impl Digest {
+    fn new(b: &[u8]) -> Digest {
+        let mut bytes = [0; 32];
+        bytes[..b.len()].copy_from_slice(b);
+        Digest { bytes, len: b.len() }
+    }
+
+    fn nibble(&self, i: usize) -> u8 {
+        let b = self.bytes[i / 2];
+        if i.is_multiple_of(2) { b >> 4 } else { b & 0xf }
+    }
+
+    /// The first `n` hex characters.
+    fn hex(&self, n: usize) -> String {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        (0..n.min(self.len * 2)).map(|i| HEX[self.nibble(i) as usize] as char).collect()
+    }
+}
+
+/// A hash function to run the tries through.
+trait Algorithm: Send + Sync {
+    fn hash(&self, input: &[u8]) -> Digest;
+
+    /// How many bytes a hash has.
+    fn size(&self) -> usize;
+
+    fn name(&self) -> &'static str;
+}
+
+struct Md5;
+
+impl Algorithm for Md5 {
+    fn hash(&self, input: &[u8]) -> Digest {
+        Digest::new(&md5::compute(input)[..])
+    }
+
+    fn size(&self) -> usize {
+        16
+    }
+
+    fn name(&self) -> &'static str {
+        "md5"
+    }
+}
+
+struct Sha1;
+
+impl Algorithm for Sha1 {
+    fn hash(&self, input: &[u8]) -> Digest {
+        Digest::new(&<sha1::Sha1 as sha1::Digest>::digest(input))
+    }
+
+    fn size(&self) -> usize {
+        20
+    }
+
+    fn name(&self) -> &'static str {
+        "sha1"
+    }
+}
+
+struct Sha256;
+
+impl Algorithm for Sha256 {
+    fn hash(&self, input: &[u8]) -> Digest {
+        Digest::new(&<sha2::Sha256 as sha2::Digest>::digest(input))
+    }
+
+    fn size(&self) -> usize {
+        32
+    }
+
+    fn name(&self) -> &'static str {
+        "sha256"
+    }
+}
+
+/// Looks up a hash algorithm by name: `md5`, `sha1` or `sha256`.
+fn algorithm(name: &str) -> Result<Arc<dyn Algorithm>, String> {
+    match name {
+        "md5" => Ok(Arc::new(Md5)),
+        "sha1" => Ok(Arc::new(Sha1)),
+        "sha256" => Ok(Arc::new(Sha256)),
+        _ => Err(format!("unknown hash algorithm '{}', expected md5, sha1 or sha256", name))
+    }
+}
+
+/// The hex prefix a hash has to start with to count as a hit.  It's kept
+/// as whole bytes plus an odd trailing nibble so it can be checked on the
+/// raw digest.
+#[derive(Clone, Debug, PartialEq)]
+struct Difficulty {
+    bytes: Vec<u8>,
+    half: Option<u8>
+}
+
+impl Difficulty {
+    /// Hashes starting with `n` zeros.
+    fn zeros(n: usize) -> Difficulty {
+        Difficulty { bytes: vec![0; n / 2], half: if n % 2 == 1 { Some(0) } else { None } }
+    }
+
+    /// Hashes starting with the hex digits in `prefix`.
+    fn prefix(prefix: &str) -> Result<Difficulty, String> {
+        let nibbles = prefix.chars()
+                            .map(|c| c.to_digit(16)
+                                      .map(|d| d as u8)
+                                      .ok_or_else(|| format!("'{}' in prefix '{}' isn't a hex digit", c, prefix)))
+                            .collect::<Result<Vec<u8>, String>>()?;
+        let pairs = nibbles.chunks_exact(2);
+        let half = pairs.remainder().first().cloned();
+        Ok(Difficulty { bytes: pairs.map(|p| p[0] << 4 | p[1]).collect(), half })
+    }
+
+    /// How many hex characters the prefix has.
+    fn nibbles(&self) -> usize {
+        self.bytes.len() * 2 + self.half.map_or(0, |_| 1)
+    }
+
+    fn matches(&self, d: &Digest) -> bool {
+        let n = self.bytes.len();
+        d.bytes[..n] == self.bytes[..] && self.half.is_none_or(|h| d.bytes[n] >> 4 == h)
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Difficulty {
+        Difficulty::zeros(5)
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in &self.bytes {
+            write!(f, "{:02x}", b)?
+        }
+        match self.half {
+            Some(h) => write!(f, "{:x}", h),
+            None => Ok(())
+        }
+    }
+}
+
+/// How the passwords are read from the hits.  Part one takes the first
+/// character after the prefix from the first `length` hits.  Part two has
+/// a slot for each of `positions`; a hit whose first character names a
+/// slot fills it with its second, unless it's already filled.
+#[derive(Clone, Debug, PartialEq)]
+struct Rules {
+    length: usize,
+    positions: Vec<char>
+}
+
+impl Rules {
+    fn new(length: usize, positions: &str) -> Result<Rules, String> {
+        let positions: Vec<char> = positions.chars().collect();
+        if length == 0 || positions.is_empty() {
+            return Err("passwords can't be empty".to_string())
+        }
+        if let Some(c) = positions.iter().find(|c| !c.is_ascii_hexdigit() || c.is_ascii_uppercase()) {
+            return Err(format!("position '{}' can't appear in a lowercase hex hash", c))
+        }
+        if let Some(c) = positions.iter().enumerate().find(|&(i, c)| positions[..i].contains(c)).map(|(_, c)| c) {
+            return Err(format!("position '{}' is given twice", c))
+        }
+        Ok(Rules { length, positions })
+    }
+
+    /// Passwords of `length` characters, with part two's slots named by the
+    /// first `length` hex digits, as in the puzzle.
+    fn with_length(length: usize) -> Result<Rules, String> {
+        if length > 16 {
+            return Err(format!("a password of {} needs '--positions', there are only 16 hex digits", length))
+        }
+        Rules::new(length, &"0123456789abcdef"[..length])
+    }
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules::with_length(8).unwrap()
+    }
+}
+
+/// Everything that decides which hits a search finds and how the
+/// passwords are read from them.
+#[derive(Clone)]
+struct Config {
+    algorithm: Arc<dyn Algorithm>,
+    difficulty: Difficulty,
+    rules: Rules
+}
+
+impl Config {
+    fn new(algorithm: Arc<dyn Algorithm>, difficulty: Difficulty, rules: Rules) -> Result<Config, String> {
+        if difficulty.nibbles() + 2 > algorithm.size() * 2 {
+            return Err(format!("prefix '{}' leaves no room for the password in a {} hash", difficulty, algorithm.name()))
+        }
+        Ok(Config { algorithm, difficulty, rules })
+    }
+
+    /// Names the hits this finds for `seed`, for keying the cache.  The
+    /// rules don't change the hits, so they're left out.
+    fn key(&self, seed: &str) -> String {
+        format!("{}:{}:{}", self.algorithm.name(), self.difficulty, seed)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config { algorithm: Arc::new(Md5), difficulty: Difficulty::default(), rules: Rules::default() }
+    }
+}
 
 struct PasswordGenerator {
     seed: String,
-    counter: u32,
+    counter: u64,
 }
 
 impl PasswordGenerator {
@@ -12,9 +249,15 @@ impl PasswordGenerator {
     }
 
     fn compute_try(&self) -> String {
-        let mut s = self.seed.clone();
-        s.push_str(&format!("{}", self.counter));
-        compute_password_try(&s)
+        let mut buf = Vec::new();
+        hash_try(&mut buf, self.seed.as_bytes(), self.counter, &Md5).hex(7)
+    }
+
+    /// Hashes the current counter into `buf` and moves on to the next.
+    fn next_digest(&mut self, buf: &mut Vec<u8>, alg: &dyn Algorithm) -> Digest {
+        let d = hash_try(buf, self.seed.as_bytes(), self.counter, alg);
+        self.next_try();
+        d
     }
 
     fn next_try(&mut self) {
@@ -32,60 +275,593 @@ impl Iterator for PasswordGenerator {
     }
 }
 
+/// Counters each thread checks per batch.
+const BATCH: u64 = 50_000;
+
+/// Finds valid tries using several threads.  Each batch splits the next
+/// `threads * BATCH` counters between the threads, and the tries they find
+/// are queued in counter order, so the results match a single threaded
+/// search.
+struct ParallelSearch {
+    seed: String,
+    threads: usize,
+    counter: u64,
+    found: VecDeque<(u64, String)>,
+    config: Config
+}
+
+impl ParallelSearch {
+    #[cfg(test)]
+    fn new(seed: &str, threads: usize) -> ParallelSearch {
+        ParallelSearch::with_config(seed, threads, &Config::default())
+    }
+
+    fn with_config(seed: &str, threads: usize, config: &Config) -> ParallelSearch {
+        ParallelSearch { seed: seed.to_string(), threads: threads.max(1), counter: 0, found: VecDeque::new(),
+                         config: config.clone() }
+    }
+
+    fn search_batch(&mut self) {
+        let seed = &self.seed;
+        let config = &self.config;
+        let start = self.counter;
+        let results: Vec<Vec<(u64, String)>> = thread::scope(|scope| {
+            let handles: Vec<_> =
+                (0..self.threads as u64).map(|t| {
+                                            let from = start + t * BATCH;
+                                            scope.spawn(move || search_range(seed, from, from + BATCH, config))
+                                        })
+                                        .collect();
+            handles.into_iter().map(|h| h.join().expect("search thread panicked")).collect()
+        });
+
+        self.counter += self.threads as u64 * BATCH;
+        for r in results {
+            self.found.extend(r)
+        }
+    }
+
+    /// The next hit already found, if any.  When this is `None` every hit
+    /// below `counter` has been handed out.
+    fn next_hit(&mut self) -> Option<Hit> {
+        self.found.pop_front().map(|(counter, t)| Hit::new(counter, &t))
+    }
+}
+
+impl Iterator for ParallelSearch {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        while self.found.is_empty() {
+            self.search_batch()
+        }
+        self.found.pop_front().map(|(_, t)| t)
+    }
+}
+
+fn default_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Hashes `seed` followed by the decimal `counter`.  The input is built in
+/// `buf`, so a caller looping over counters can reuse the one allocation.
+fn hash_try(buf: &mut Vec<u8>, seed: &[u8], counter: u64, alg: &dyn Algorithm) -> Digest {
+    buf.clear();
+    buf.extend_from_slice(seed);
+    write!(buf, "{}", counter).expect("writing to a Vec can't fail");
+    alg.hash(buf)
+}
+
+/// Valid tries for the counters `from..to` with their counters, in order.
+/// A try is the prefix and the two characters after it.  Only hits are
+/// formatted; every other counter just hashes into the shared buffer.
+fn search_range(seed: &str, from: u64, to: u64, config: &Config) -> Vec<(u64, String)> {
+    let mut gen = PasswordGenerator { counter: from, ..PasswordGenerator::new(seed) };
+    let mut buf = Vec::with_capacity(seed.len() + 10);
+    let mut found = Vec::new();
+    while gen.counter < to {
+        let counter = gen.counter;
+        let d = gen.next_digest(&mut buf, &*config.algorithm);
+        if config.difficulty.matches(&d) {
+            found.push((counter, d.hex(config.difficulty.nibbles() + 2)))
+        }
+    }
+    found
+}
+
+#[cfg(test)]
 fn compute_password_try(s: &str) -> String {
-    format!("{:.7}", format!("{:x}", md5::compute(s)))
+    Md5.hash(s.as_bytes()).hex(7)
 }
 
+#[cfg(test)]
 fn is_valid_try(s: &str) -> bool {
     s.starts_with("00000")
 }
 
-fn crack_password_pt1(s: &str) -> String {
-    PasswordGenerator::new(s)
-                      .filter(|p| is_valid_try(p))
-                      .take(8)
-                      .map(|s| s.chars().nth(5).unwrap())
-                      .collect()
+/// A counter whose hash starts with the prefix, along with the two hex
+/// characters after it, which is all either part needs.  `pos` is part
+/// one's password character and part two's position, and `val` is part
+/// two's character.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Hit {
+    counter: u64,
+    pos: char,
+    val: char
 }
 
-fn replace_null(vec: &Vec<u8>) -> Vec<u8> {
-    let mut vec2 = Vec::new();
+impl Hit {
+    /// The hit for a try, which ends with the two characters after the
+    /// prefix.
+    fn new(counter: u64, t: &str) -> Hit {
+        let mut cs = t.chars().rev();
+        let val = cs.next().expect("try is too short");
+        let pos = cs.next().expect("try is too short");
+        Hit { counter, pos, val }
+    }
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}{}", self.counter, self.pos, self.val)
+    }
+}
 
-    for v in vec {
-        let v = if *v == 0 { 32 } else { *v };
-        vec2.push(v)
+impl FromStr for Hit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Hit, String> {
+        let mut fields = s.split_whitespace();
+        let counter = fields.next()
+                            .ok_or_else(|| format!("missing counter in '{}'", s))?
+                            .parse()
+                            .map_err(|e| format!("bad counter in '{}': {}", s, e))?;
+        let hex: Vec<char> = fields.next().map(|h| h.chars().collect()).unwrap_or_default();
+        if hex.len() != 2 || !hex.iter().all(|c| c.is_ascii_hexdigit()) || fields.next().is_some() {
+            return Err(format!("expected a counter and two hex characters, got '{}'", s))
+        }
+        Ok(Hit { counter, pos: hex[0], val: hex[1] })
     }
-    vec2
 }
 
-fn crack_password_pt2(s: &str) -> String {
-    let lo = b'0';
-    let hi = b'7';
-    let mut passwd: [u8; 8] = [0; 8];
-    let mut count = 0;
-    let mut gen =
-        PasswordGenerator::new(s)
-                          .filter(|p| is_valid_try(p))
-                          .map(|s| {
-                              let b = s.as_bytes();
-                              (b[5], b[6])
-                          })
-                          .filter(|&(i, _)| lo <= i && i <= hi)
-                          .map(|(i, j)| (i - lo, j));
+/// Builds both passwords at once from hits fed in counter order, keeping
+/// the hits so they can be cached.
+struct Solver {
+    rules: Rules,
+    hits: Vec<Hit>,
+    first: String,
+    second: Vec<u8>,
+    found: usize
+}
+
+impl Solver {
+    #[cfg(test)]
+    fn new() -> Solver {
+        Solver::with_rules(Rules::default())
+    }
+
+    fn with_rules(rules: Rules) -> Solver {
+        let second = vec![0; rules.positions.len()];
+        Solver { rules, hits: Vec::new(), first: String::new(), second, found: 0 }
+    }
 
-    while let Some((loc, val)) = gen.next() {
-        let loc = loc as usize;
-        if passwd[loc] == 0 {
-            passwd[loc] = val;
-            count += 1;
-            println!("{}", String::from_utf8(replace_null(&passwd.to_vec())).unwrap());
+    fn add(&mut self, h: Hit) {
+        if self.first.len() < self.rules.length {
+            self.first.push(h.pos)
+        }
+        if let Some(loc) = self.rules.positions.iter().position(|&p| p == h.pos) {
+            if self.second[loc] == 0 {
+                self.second[loc] = h.val as u8;
+                self.found += 1
+            }
         }
+        self.hits.push(h)
+    }
+
+    fn first(&self) -> Option<&str> {
+        if self.first.len() == self.rules.length { Some(&self.first) } else { None }
+    }
 
-        if count == 8 {
-            return String::from_utf8(passwd.to_vec()).expect("encoding weirdness happened")
+    fn second(&self) -> Option<String> {
+        if self.found == self.second.len() {
+            Some(String::from_utf8(self.second.to_vec()).expect("encoding weirdness happened"))
+        } else {
+            None
         }
     }
-    "".to_string()
+
+    /// Part one's password so far, with `None` for the characters still
+    /// to find.
+    fn first_slots(&self) -> Vec<Option<char>> {
+        let mut cs = self.first.chars();
+        (0..self.rules.length).map(|_| cs.next()).collect()
+    }
+
+    /// Part two's password so far, with `None` for the slots still empty.
+    fn second_slots(&self) -> Vec<Option<char>> {
+        self.second.iter().map(|&b| if b == 0 { None } else { Some(b as char) }).collect()
+    }
+
+    /// The counter after the last hit, which is as far as the solver is
+    /// known to have checked.
+    fn checked(&self) -> u64 {
+        self.hits.last().map_or(0, |h| h.counter + 1)
+    }
+
+    fn both_done(&self) -> bool {
+        self.first().is_some() && self.second().is_some()
+    }
+}
+
+/// How far a search has got: every counter below `counter` has been
+/// checked and its hits are in `hits`.  The hits are all it takes to
+/// rebuild both partial passwords.
+#[derive(Debug, Default, PartialEq)]
+struct Checkpoint {
+    counter: u64,
+    hits: Vec<Hit>
+}
+
+impl Checkpoint {
+    /// A checkpoint just after the last of `hits`, which must be every hit
+    /// up to it, as in a cache file.
+    fn after(hits: Vec<Hit>) -> Checkpoint {
+        Checkpoint { counter: hits.last().map_or(0, |h| h.counter + 1), hits }
+    }
+
+    /// Reads a checkpoint written by `write_checkpoint`: the counter on the
+    /// first line and then one hit per line.
+    fn read(path: &Path) -> Result<Checkpoint, String> {
+        let f = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut lines = BufReader::new(f).lines();
+        let counter = lines.next()
+                           .ok_or_else(|| format!("{}: empty checkpoint", path.display()))?
+                           .map_err(|e| e.to_string())?;
+        let counter = counter.trim()
+                             .parse()
+                             .map_err(|e| format!("{}: bad counter '{}': {}", path.display(), counter, e))?;
+        let hits = lines.map(|l| l.map_err(|e| e.to_string()).and_then(|l| l.parse()))
+                        .collect::<Result<Vec<Hit>, String>>()?;
+        Ok(Checkpoint { counter, hits })
+    }
+}
+
+/// Writes a checkpoint to a temporary file first and then renames it over
+/// `path`, so an interrupted write never leaves a broken checkpoint.
+fn write_checkpoint(path: &Path, counter: u64, hits: &[Hit]) -> io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut out = BufWriter::new(File::create(&tmp)?);
+        writeln!(out, "{}", counter)?;
+        for h in hits {
+            writeln!(out, "{}", h)?
+        }
+        out.flush()?
+    }
+    std::fs::rename(tmp, path)
+}
+
+/// Feeds the hits from `start` and then fresh ones from a search into a
+/// solver until `done` holds.  Before each new batch `on_batch` gets the
+/// counter reached so far and the solver, whose hits then cover every
+/// counter below it; if it returns false the search stops and `None` is
+/// returned.
+fn solve(seed: &str, threads: usize, config: &Config, start: Checkpoint, done: &dyn Fn(&Solver) -> bool,
+         on_batch: &mut dyn FnMut(u64, &Solver) -> bool) -> Option<Solver> {
+    let mut solver = Solver::with_rules(config.rules.clone());
+
+    for h in start.hits {
+        if done(&solver) {
+            return Some(solver)
+        }
+        solver.add(h)
+    }
+
+    let mut search = ParallelSearch { counter: start.counter, ..ParallelSearch::with_config(seed, threads, config) };
+    while !done(&solver) {
+        match search.next_hit() {
+            Some(h) => solver.add(h),
+            None => {
+                if !on_batch(search.counter, &solver) {
+                    return None
+                }
+                search.search_batch()
+            }
+        }
+    }
+    Some(solver)
+}
+
+/// Which passwords a search is after.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Part {
+    One,
+    Two,
+    Both
+}
+
+impl Part {
+    fn done(self, s: &Solver) -> bool {
+        match self {
+            Part::One => s.first().is_some(),
+            Part::Two => s.second().is_some(),
+            Part::Both => s.both_done()
+        }
+    }
+
+    /// The passwords so far, with `None` for the characters still to find.
+    fn passwords(self, s: &Solver) -> Vec<Vec<Option<char>>> {
+        match self {
+            Part::One => vec![s.first_slots()],
+            Part::Two => vec![s.second_slots()],
+            Part::Both => vec![s.first_slots(), s.second_slots()]
+        }
+    }
+
+    /// How many more hits it should take to finish.  Part one needs one per
+    /// character.  For part two a hit fills an empty slot one time in 16
+    /// for each empty slot, so filling the last `k` takes `16/k + ... + 16/1`.
+    fn expected_hits(self, s: &Solver) -> f64 {
+        let one = (s.rules.length - s.first.len()) as f64;
+        let two = (1..=s.second.len() - s.found).map(|k| 16.0 / k as f64).sum();
+        match self {
+            Part::One => one,
+            Part::Two => two,
+            Part::Both => one.max(two)
+        }
+    }
+}
+
+/// A report on how a search is going: the next counter to check, hashes
+/// per second, password characters found out of those needed, the time
+/// left at the hit rate seen so far (once there's been a hit), and the
+/// passwords themselves.
+#[derive(Clone, Debug, PartialEq)]
+struct Progress {
+    counter: u64,
+    rate: f64,
+    found: usize,
+    needed: usize,
+    eta: Option<Duration>,
+    passwords: Vec<Vec<Option<char>>>
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "counter {}  {}/{} found  {} H/s  ETA ", self.counter, self.found, self.needed, si(self.rate))?;
+        match self.eta {
+            Some(eta) => write!(f, "{}", hms(eta)),
+            None => write!(f, "?")
+        }
+    }
+}
+
+/// Turns the state of a search for `part` into `Progress` reports.
+struct Tracker {
+    part: Part,
+    started: Instant,
+    from: u64
+}
+
+impl Tracker {
+    /// A tracker for a search starting at counter `from`.
+    fn new(part: Part, from: u64) -> Tracker {
+        Tracker { part, started: Instant::now(), from }
+    }
+
+    fn progress(&self, counter: u64, s: &Solver) -> Progress {
+        self.report(counter, self.started.elapsed(), s)
+    }
+
+    fn report(&self, counter: u64, elapsed: Duration, s: &Solver) -> Progress {
+        let secs = elapsed.as_secs_f64();
+        let rate = if secs > 0.0 { counter.saturating_sub(self.from) as f64 / secs } else { 0.0 };
+        let (found, needed) = match self.part {
+            Part::One => (s.first.len(), s.rules.length),
+            Part::Two => (s.found, s.second.len()),
+            Part::Both => (s.first.len() + s.found, s.rules.length + s.second.len())
+        };
+        let eta = if s.hits.is_empty() || rate == 0.0 {
+            None
+        } else {
+            let per_hit = counter as f64 / s.hits.len() as f64;
+            Duration::try_from_secs_f64(self.part.expected_hits(s) * per_hit / rate).ok()
+        };
+        Progress { counter, rate, found, needed, eta, passwords: self.part.passwords(s) }
+    }
+}
+
+/// How often the animation redraws.
+const FRAME: Duration = Duration::from_millis(50);
+
+/// Redraws the passwords in place on one line while the search runs, with
+/// random hex cycling through the characters not found yet and the
+/// progress after them.  It only draws when stdout is a terminal.
+struct Animation {
+    enabled: bool,
+    drawn: Instant,
+    rng: u64
+}
+
+impl Animation {
+    fn new() -> Animation {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        Animation { enabled: io::stdout().is_terminal(), drawn: Instant::now(), rng: seed | 1 }
+    }
+
+    /// A random hex digit from an xorshift generator, which is plenty for
+    /// show.
+    fn random_hex(&mut self) -> char {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        std::char::from_digit((self.rng % 16) as u32, 16).unwrap()
+    }
+
+    /// Redraws if a frame is due.
+    fn tick(&mut self, p: &Progress) {
+        if self.drawn.elapsed() >= FRAME {
+            self.draw(p)
+        }
+    }
+
+    fn draw(&mut self, p: &Progress) {
+        if !self.enabled {
+            return
+        }
+        let mut line = String::from("\r");
+        for (i, pw) in p.passwords.iter().enumerate() {
+            if i > 0 {
+                line.push(' ')
+            }
+            for c in pw {
+                let c = c.unwrap_or_else(|| self.random_hex());
+                line.push(c)
+            }
+        }
+        line.push_str(&format!("  {}\x1b[K", p));
+
+        let mut out = io::stdout();
+        let _ = out.write_all(line.as_bytes()).and_then(|_| out.flush());
+        self.drawn = Instant::now();
+    }
+
+    /// Draws the last frame and ends its line, so anything printed after
+    /// goes below it.
+    fn finish(&mut self, p: &Progress) {
+        if self.enabled {
+            self.draw(p);
+            println!()
+        }
+    }
+}
+
+/// A rate with an SI suffix, like `6.2M`.
+fn si(x: f64) -> String {
+    match x {
+        x if x >= 1e9 => format!("{:.1}G", x / 1e9),
+        x if x >= 1e6 => format!("{:.1}M", x / 1e6),
+        x if x >= 1e3 => format!("{:.1}k", x / 1e3),
+        x => format!("{:.0}", x)
+    }
+}
+
+/// A duration in hours, minutes and seconds, like `1m05s`.
+fn hms(d: Duration) -> String {
+    let s = d.as_secs();
+    match s {
+        s if s >= 3600 => format!("{}h{:02}m", s / 3600, s % 3600 / 60),
+        s if s >= 60 => format!("{}m{:02}s", s / 60, s % 60),
+        s => format!("{}s", s)
+    }
+}
+
+/// Where the hits for a cache key are kept.  The file is named after a
+/// hash of the key so any seed gives a usable file name.
+fn cache_path(key: &str) -> PathBuf {
+    env::temp_dir().join(format!("aoc-day05-{:x}.hits", md5::compute(key)))
+}
+
+/// Where an unfinished search for a cache key keeps its checkpoint.
+fn checkpoint_path(key: &str) -> PathBuf {
+    cache_path(key).with_extension("checkpoint")
+}
+
+/// How often a long search saves a checkpoint.
+const CHECKPOINT_EVERY: Duration = Duration::from_secs(10);
+
+/// Reads cached hits, one per line.  Reading stops at the first line that
+/// doesn't parse, since the hits before it are still a complete prefix.
+fn read_cache(path: &Path) -> Vec<Hit> {
+    match File::open(path) {
+        Ok(f) => BufReader::new(f).lines()
+                                  .map_while(|l| l.ok().and_then(|l| l.parse().ok()))
+                                  .collect(),
+        Err(_) => Vec::new()
+    }
+}
+
+fn write_cache(path: &Path, hits: &[Hit]) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    for h in hits {
+        writeln!(out, "{}", h)?
+    }
+    out.flush()
+}
+
+/// How often `--progress` reports.
+const PROGRESS_EVERY: Duration = Duration::from_secs(1);
+
+/// Searches until `part` is solved, starting from the cached hits for
+/// `seed` and saving any new ones, then prints its answers.  With
+/// `resume` it starts from the last checkpoint instead.  A checkpoint is
+/// saved every so often during the search, and on Ctrl-C before exiting.
+/// With `report` the progress goes to stderr as well as the animation.
+fn solve_cached(seed: &str, threads: usize, config: &Config, part: Part, resume: bool, report: bool) {
+    let path = cache_path(&config.key(seed));
+    let ckpt_path = checkpoint_path(&config.key(seed));
+    let start = if resume {
+        Checkpoint::read(&ckpt_path).unwrap_or_else(|e| {
+            eprintln!("can't resume, {}", e);
+            Checkpoint::after(read_cache(&path))
+        })
+    } else {
+        Checkpoint::after(read_cache(&path))
+    };
+    let n = start.hits.len();
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag_interrupt = interrupted.clone();
+    ctrlc::set_handler(move || flag_interrupt.store(true, Ordering::SeqCst)).expect("couldn't set Ctrl-C handler");
+
+    let tracker = Tracker::new(part, start.counter);
+    let mut animation = Animation::new();
+    let mut saved = Instant::now();
+    let mut reported = Instant::now();
+    let mut reached = 0;
+    let solver = solve(seed, threads, config, start, &|s| part.done(s), &mut |counter, s| {
+        let stop = interrupted.load(Ordering::SeqCst);
+        reached = counter;
+        let progress = tracker.progress(counter, s);
+        animation.tick(&progress);
+        if report && reported.elapsed() >= PROGRESS_EVERY {
+            eprintln!("{}", progress);
+            reported = Instant::now()
+        }
+        if stop || saved.elapsed() >= CHECKPOINT_EVERY {
+            if let Err(e) = write_checkpoint(&ckpt_path, counter, &s.hits) {
+                eprintln!("couldn't write {}: {}", ckpt_path.display(), e)
+            }
+            saved = Instant::now();
+        }
+        !stop
+    });
+
+    let solver = match solver {
+        Some(solver) => solver,
+        None => {
+            if animation.enabled {
+                println!()
+            }
+            eprintln!("interrupted at counter {}, run again with '--resume' to carry on", reached);
+            std::process::exit(130)
+        }
+    };
+
+    if solver.hits.len() > n {
+        if let Err(e) = write_cache(&path, &solver.hits) {
+            eprintln!("couldn't write {}: {}", path.display(), e)
+        }
+    }
+    let _ = std::fs::remove_file(&ckpt_path);
+    animation.finish(&tracker.progress(reached.max(solver.checked()), &solver));
+
+    if part != Part::Two {
+        println!("{}", solver.first().unwrap())
+    }
+    if part != Part::One {
+        println!("{}", solver.second().unwrap())
+    }
 }
 
 #[test]
@@ -97,28 +873,199 @@ fn aoc05_test_compute_password_try() {
 #[test]
 fn aoc05_test_compute_try() {
     let x = PasswordGenerator::new("abc");
-    assert!(x.compute_try() == "577571")
+    assert!(x.compute_try() == "577571b")
 }
 
 #[test]
 fn aoc05_test_password_generator_iterator() {
     let mut x = PasswordGenerator { seed: "abc".to_string(), counter: 3231928 }
                     .filter(|p| is_valid_try(p));
-    assert_eq!(x.next(), Some("000001".to_string()))
+    assert_eq!(x.next(), Some("0000015".to_string()))
+}
+
+#[test]
+fn aoc05_test_parallel_search() {
+    let seq: Vec<String> = PasswordGenerator { seed: "abc".to_string(), counter: 3100000 }
+                               .filter(|p| is_valid_try(p))
+                               .take(2)
+                               .collect();
+    for threads in 1..5 {
+        let par: Vec<String> = ParallelSearch { counter: 3100000, ..ParallelSearch::new("abc", threads) }
+                                   .take(2)
+                                   .collect();
+        assert_eq!(par, seq);
+    }
 }
 
 #[test]
 fn aoc05_test_password_generator_pt1() {
-    assert_eq!(crack_password("abc"), "18f47a30");
+    let solver = solve("abc", 4, &Config::default(), Checkpoint::default(), &|s| Part::One.done(s), &mut |_, _| true);
+    assert_eq!(solver.unwrap().first(), Some("18f47a30"));
 }
 
-fn main() {
-    let flag = std::env::args().nth(1).unwrap();
+#[test]
+fn aoc05_test_solver() {
+    let hits: Vec<Hit> = ["1 5a", "2 1b", "3 1c", "4 8d", "5 0e", "6 2f", "7 30", "8 41", "9 52", "10 63", "11 74"]
+                             .iter()
+                             .map(|h| h.parse().unwrap())
+                             .collect();
+    let mut solver = Solver::new();
+    for (i, h) in hits.into_iter().enumerate() {
+        solver.add(h);
+        assert_eq!(solver.first().is_some(), i >= 7);
+    }
+    assert_eq!(solver.first(), Some("51180234"));
+    assert_eq!(solver.second(), Some("ebf01a34".to_string()));
+    assert!(solver.both_done());
+    assert!("12 5".parse::<Hit>().is_err());
+    assert!("x 5a".parse::<Hit>().is_err());
+}
+
+#[test]
+fn aoc05_test_hit_cache() {
+    let hits = vec![Hit::new(3231929, "0000015"), Hit::new(5017308, "000008f")];
+    let path = env::temp_dir().join(format!("aoc05_test_hit_cache-{}.hits", std::process::id()));
+    write_cache(&path, &hits).unwrap();
+    assert_eq!(read_cache(&path), hits);
+
+    std::fs::write(&path, "3231929 15\nnonsense\n5017308 8f\n").unwrap();
+    assert_eq!(read_cache(&path), &hits[..1]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(read_cache(&path).is_empty());
+}
+
+#[test]
+fn aoc05_test_difficulty_and_algorithms() {
+    assert_eq!(Sha1.hash(b"abc").hex(40), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(Sha256.hash(b"abc").hex(64), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    assert_eq!(Difficulty::zeros(5), Difficulty::prefix("00000").unwrap());
+    assert_eq!(Difficulty::prefix("a9993").unwrap().to_string(), "a9993");
+    assert!(Difficulty::prefix("a9993").unwrap().matches(&Sha1.hash(b"abc")));
+    assert!(!Difficulty::prefix("a9994").unwrap().matches(&Sha1.hash(b"abc")));
+    assert!(Difficulty::prefix("a9g").is_err());
 
-    match flag.as_ref() {
-        "-1" => println!("{}", crack_password_pt1("cxdnnyjw")),
-        "-2" => println!("{}", crack_password_pt2("cxdnnyjw")),
-        _ => println!("expected '-1' or '-2'")
+    let config = Config::new(Arc::new(Sha256), Difficulty::prefix("ab").unwrap(), Rules::default()).unwrap();
+    let expected: Vec<(u64, String)> = (0..5000).map(|c| (c, Sha256.hash(format!("abc{}", c).as_bytes()).hex(4)))
+                                                .filter(|(_, t)| t.starts_with("ab"))
+                                                .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(search_range("abc", 0, 5000, &config), expected);
+    assert!(Config::new(Arc::new(Md5), Difficulty::zeros(31), Rules::default()).is_err());
+
+    assert!(Rules::with_length(17).is_err());
+    assert!(Rules::new(4, "0g").is_err());
+    assert!(Rules::new(4, "010").is_err());
+    let mut solver = Solver::with_rules(Rules::new(3, "a0").unwrap());
+    for h in &["1 a5", "2 0c", "3 a7"] {
+        solver.add(h.parse().unwrap())
     }
+    assert_eq!(solver.first(), Some("a0a"));
+    assert_eq!(solver.second(), Some("5c".to_string()));
+}
+
+#[test]
+fn aoc05_test_progress() {
+    let mut solver = Solver::new();
+    solver.add("100 5a".parse().unwrap());
+    solver.add("200 1b".parse().unwrap());
+    let secs = Duration::from_secs(1);
+
+    let p = Tracker::new(Part::One, 0).report(1000, secs, &solver);
+    assert_eq!((p.counter, p.rate, p.found, p.needed), (1000, 1000.0, 2, 8));
+    assert_eq!(p.eta, Some(Duration::from_secs(3)));
+    assert_eq!(p.passwords, vec![vec![Some('5'), Some('1'), None, None, None, None, None, None]]);
+    assert_eq!(p.to_string(), "counter 1000  2/8 found  1.0k H/s  ETA 3s");
+
+    // Six empty slots take 16 * (1 + 1/2 + ... + 1/6) = 39.2 hits.
+    let p = Tracker::new(Part::Two, 500).report(1000, secs, &solver);
+    assert_eq!((p.rate, p.found, p.needed), (500.0, 2, 8));
+    assert_eq!(p.eta.map(|d| d.as_secs()), Some(39));
+    let p = Tracker::new(Part::Both, 0).report(1000, secs, &solver);
+    assert_eq!((p.found, p.needed, p.passwords.len()), (4, 16, 2));
+    assert_eq!(p.eta.map(|d| d.as_secs()), Some(19));
+
+    assert_eq!(Tracker::new(Part::One, 0).report(1000, secs, &Solver::new()).eta, None);
+    assert_eq!(hms(Duration::from_secs(3725)), "1h02m");
+    assert_eq!(hms(Duration::from_secs(65)), "1m05s");
+}
+
+#[test]
+fn aoc05_test_checkpoint() {
+    let path = env::temp_dir().join(format!("aoc05_test_checkpoint-{}.checkpoint", std::process::id()));
+    let hits = vec![Hit::new(3231929, "0000015")];
+    write_checkpoint(&path, 4000000, &hits).unwrap();
+    let ckpt = Checkpoint::read(&path).unwrap();
+    assert_eq!(ckpt, Checkpoint { counter: 4000000, hits: hits.clone() });
+    std::fs::remove_file(&path).unwrap();
+    assert!(Checkpoint::read(&path).is_err());
+    assert_eq!(Checkpoint::after(hits).counter, 3231930);
+
+    // Resuming past the first hit finds the second, and a search that's
+    // told to stop gives up at the next batch.  An easy difficulty keeps
+    // the searches short.
+    let config = Config::new(Arc::new(Md5), Difficulty::prefix("000").unwrap(), Rules::default()).unwrap();
+    let found: Vec<Hit> = search_range("abc", 0, BATCH, &config).iter().map(|(c, t)| Hit::new(*c, t)).collect();
+    assert!(found.len() >= 2);
+    let second = |s: &Solver| s.hits.len() == 2;
+    let solver = solve("abc", 2, &config, Checkpoint::after(found[..1].to_vec()), &second, &mut |_, _| true).unwrap();
+    assert_eq!(solver.hits, &found[..2]);
+    let mut batches = Vec::new();
+    let stopped = solve("abc", 2, &config, Checkpoint::default(), &|_| false, &mut |c, _| {
+        batches.push(c);
+        batches.len() < 3
+    });
+    assert!(stopped.is_none());
+    assert_eq!(batches, vec![0, 2 * BATCH, 4 * BATCH]);
+}
+
+/// The search picked with `--hash`, `--zeros` or `--prefix`, `--length`
+/// and `--positions`.
+fn config(args: &[String]) -> Result<Config, String> {
+    let value = |name: &str| args.iter()
+                                 .position(|a| a == name)
+                                 .and_then(|i| args.get(i + 1));
+    let algorithm = match value("--hash") {
+        Some(h) => algorithm(h)?,
+        None => Arc::new(Md5)
+    };
+    let difficulty = match (value("--zeros"), value("--prefix")) {
+        (Some(_), Some(_)) => return Err("expected only one of '--zeros' and '--prefix'".to_string()),
+        (Some(n), None) => Difficulty::zeros(n.parse().map_err(|_| format!("expected a number of zeros, got {:?}", n))?),
+        (None, Some(p)) => Difficulty::prefix(p)?,
+        (None, None) => Difficulty::default()
+    };
+    let length = match value("--length") {
+        Some(n) => Some(n.parse().map_err(|_| format!("expected a password length, got {:?}", n))?),
+        None => None
+    };
+    let rules = match (length, value("--positions")) {
+        (length, Some(p)) => Rules::new(length.unwrap_or(p.chars().count()), p)?,
+        (Some(n), None) => Rules::with_length(n)?,
+        (None, None) => Rules::default()
+    };
+    Config::new(algorithm, difficulty, rules)
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = args.first().expect("expected '-1', '-2' or '-b'");
+    let threads = match args.iter().position(|a| a == "-t") {
+        Some(i) => args.get(i + 1)
+                       .and_then(|t| t.parse().ok())
+                       .expect("expected a thread count after '-t'"),
+        None => default_threads()
+    };
+    let has_flag = |name: &str| args.iter().any(|a| a == name);
+    let config = match config(&args) {
+        Ok(c) => c,
+        Err(e) => return println!("error: {}", e)
+    };
 
+    let part = match flag.as_ref() {
+        "-1" => Part::One,
+        "-2" => Part::Two,
+        "-b" => Part::Both,
+        _ => return println!("expected '-1', '-2' or '-b'")
+    };
+    solve_cached("cxdnnyjw", threads, &config, part, has_flag("--resume"), has_flag("--progress"))
 }
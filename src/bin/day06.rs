@@ -1,65 +1,462 @@
 
 use std::collections::HashMap;
-use std::io::BufRead;
+use std::fmt;
+use std::cmp::Reverse;
+use std::io::{BufRead, IsTerminal, Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 
+/// Whether a column's message character is its most or least common one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Pick {
+    Most,
+    Least
+}
+
+impl Pick {
+    fn decoder(self) -> fn(&Counter) -> char {
+        match self {
+            Pick::Most => Counter::top,
+            Pick::Least => Counter::bottom
+        }
+    }
+}
+
+/// Counts of each character.  ASCII is counted in a fixed array, so adding
+/// a character is a single increment and finding the top or bottom one is
+/// a scan of the alphabet; anything else goes in a map.
 #[derive(Debug)]
 struct Counter {
-    counts: HashMap<char, u32>
+    ascii: [u32; 128],
+    other: HashMap<char, u32>
 }
 
 impl Counter {
     fn new() -> Counter {
-        Counter { counts: HashMap::new() }
+        Counter { ascii: [0; 128], other: HashMap::new() }
     }
 
     fn add_char(&mut self, ch: char) {
-        let c = self.counts.entry(ch).or_insert(0);
-        *c += 1;
+        if ch.is_ascii() {
+            self.ascii[ch as usize] += 1
+        } else {
+            *self.other.entry(ch).or_insert(0) += 1
+        }
+    }
+
+    /// Adds the counts from `other`, as when counting in chunks.
+    fn merge(&mut self, other: &Counter) {
+        for (c, o) in self.ascii.iter_mut().zip(other.ascii.iter()) {
+            *c += o
+        }
+        for (&ch, &c) in &other.other {
+            *self.other.entry(ch).or_insert(0) += c
+        }
+    }
+
+    /// Every character seen with its count.
+    fn counts(&self) -> impl Iterator<Item = (char, u32)> + '_ {
+        self.ascii.iter()
+                  .enumerate()
+                  .filter(|&(_, &c)| c > 0)
+                  .map(|(i, &c)| (i as u8 as char, c))
+                  .chain(self.other.iter().map(|(&ch, &c)| (ch, c)))
+    }
+
+    /// The best character for `pick`, with ties going to the first
+    /// alphabetically.
+    fn best(&self, pick: Pick) -> char {
+        let best = match pick {
+            Pick::Most => self.counts().min_by_key(|&(ch, c)| (Reverse(c), ch)),
+            Pick::Least => self.counts().min_by_key(|&(ch, c)| (c, ch))
+        };
+        best.expect("empty column").0
+    }
+
+    /// The characters with their counts, best first for `pick`.  Equal
+    /// counts go in alphabetical order, so the result doesn't depend on
+    /// how the map iterates.
+    fn ranked(&self, pick: Pick) -> Vec<(char, u32)> {
+        let mut entries: Vec<(char, u32)> = self.counts().collect();
+        match pick {
+            Pick::Most => entries.sort_by_key(|&(ch, c)| (Reverse(c), ch)),
+            Pick::Least => entries.sort_by_key(|&(ch, c)| (c, ch))
+        }
+        entries
     }
 
     fn top(&self) -> char {
-        let mut entries: Vec<(char, u32)> = self.counts.iter().map(|(ch, c)| (*ch, *c)).collect();
-        entries.sort_by(|&(_, a), &(_, b)| b.cmp(&a));
-        entries[0].0
+        self.best(Pick::Most)
     }
 
     fn bottom(&self) -> char {
-        let mut entries: Vec<(char, u32)> = self.counts.iter().map(|(ch, c)| (*ch, *c)).collect();
-        entries.sort_by(|&(_, a), &(_, b)| a.cmp(&b));
-        entries[0].0
+        self.best(Pick::Least)
+    }
+
+    /// Each character with the probability that it's the one `pick` wants,
+    /// best first, and whether its count is tied with another character's.
+    /// For the most common character that's its share of the count; for
+    /// the least common it's its share of the inverse counts.
+    fn distribution(&self, pick: Pick) -> Vec<(char, f64, bool)> {
+        let ranked = self.ranked(pick);
+        let weight = |c: u32| match pick {
+            Pick::Most => c as f64,
+            Pick::Least => 1.0 / c as f64
+        };
+        let total: f64 = ranked.iter().map(|&(_, c)| weight(c)).sum();
+        ranked.iter()
+              .map(|&(ch, c)| (ch, weight(c) / total, ranked.iter().any(|&(o, oc)| o != ch && oc == c)))
+              .collect()
     }
 }
 
-fn part_one() {
-    let stdin = std::io::stdin();
-    let mut cs: Vec<Counter> = (0..8).map(|_| Counter::new()).collect();
+/// A decoded message with the probability of each of its characters in
+/// its column, and which of those characters were tied with another.
+#[derive(Clone, Debug, PartialEq)]
+struct Candidate {
+    message: String,
+    columns: Vec<f64>,
+    tied: Vec<bool>
+}
 
-    for line in stdin.lock().lines() {
-        for (i, ch) in line.unwrap().chars().enumerate() {
-            cs[i].add_char(ch)
+impl Candidate {
+    /// The probability of the whole message, taking the columns as
+    /// independent.
+    fn confidence(&self) -> f64 {
+        self.columns.iter().product()
+    }
+
+    fn is_tied(&self) -> bool {
+        self.tied.iter().any(|&t| t)
+    }
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:.3e}", self.message, self.confidence())?;
+        for (p, &t) in self.columns.iter().zip(&self.tied) {
+            write!(f, " {:.2}{}", p, if t { "*" } else { "" })?
         }
-        println!("{}", cs.iter().map(|c| c.top()).collect::<String>());
+        Ok(())
     }
 }
 
-fn part_two() {
+/// A counter for each column of the message.  The width is taken from the
+/// first line.  Other lines have to match it unless `pad` is set, in which
+/// case short lines leave gaps that aren't counted and long lines add
+/// columns.
+#[derive(Debug)]
+struct Columns {
+    cs: Vec<Counter>,
+    pad: bool,
+    lines: usize
+}
+
+impl Columns {
+    fn new(pad: bool) -> Columns {
+        Columns { cs: Vec::new(), pad, lines: 0 }
+    }
+
+    fn add_line(&mut self, line: &str) -> Result<(), String> {
+        let width = line.chars().count();
+        if self.lines == 0 || self.pad {
+            while self.cs.len() < width {
+                self.cs.push(Counter::new())
+            }
+        } else if width != self.cs.len() {
+            return Err(format!("line {}: expected {} characters, got {}", self.lines + 1, self.cs.len(), width))
+        }
+
+        for (i, ch) in line.chars().enumerate() {
+            self.cs[i].add_char(ch)
+        }
+        self.lines += 1;
+        Ok(())
+    }
+
+    /// Adds the counts from `other`, which covers later lines.
+    fn merge(&mut self, other: Columns) {
+        while self.cs.len() < other.cs.len() {
+            self.cs.push(Counter::new())
+        }
+        for (c, o) in self.cs.iter_mut().zip(other.cs.iter()) {
+            c.merge(o)
+        }
+    }
+
+    fn decode(&self, pick: fn(&Counter) -> char) -> String {
+        self.cs.iter().map(pick).collect()
+    }
+
+    /// The `k` most likely messages, best first.  A beam of the best `k`
+    /// prefixes is extended a column at a time, which is exact since the
+    /// columns are independent.  Messages with the same confidence go in
+    /// alphabetical order.
+    fn n_best(&self, pick: Pick, k: usize) -> Vec<Candidate> {
+        let mut beam = vec![Candidate { message: String::new(), columns: Vec::new(), tied: Vec::new() }];
+        for c in &self.cs {
+            let dist = c.distribution(pick);
+            let mut next = Vec::new();
+            for cand in &beam {
+                for &(ch, p, tied) in dist.iter().take(k) {
+                    let mut cand = cand.clone();
+                    cand.message.push(ch);
+                    cand.columns.push(p);
+                    cand.tied.push(tied);
+                    next.push(cand)
+                }
+            }
+            next.sort_by(|a, b| b.confidence()
+                                 .partial_cmp(&a.confidence())
+                                 .unwrap()
+                                 .then_with(|| a.message.cmp(&b.message)));
+            next.truncate(k);
+            beam = next
+        }
+        beam
+    }
+}
+
+/// Counts every line of `input`, calling `each` after each line.
+fn read_columns(input: &mut dyn BufRead, pad: bool, each: &mut dyn FnMut(&Columns)) -> Result<Columns, String> {
+    let mut cols = Columns::new(pad);
+    for line in input.lines() {
+        cols.add_line(&line.map_err(|e| e.to_string())?)?;
+        each(&cols)
+    }
+    Ok(cols)
+}
+
+/// Counts the lines of `text` like `read_columns`, but split into `threads`
+/// chunks that are counted at the same time and then merged.  Every chunk
+/// starts from the width of the first line, and errors report the line
+/// number in the whole text.
+fn count_parallel(text: &str, pad: bool, threads: usize) -> Result<Columns, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let width = lines.first().map_or(0, |l| l.chars().count());
+    let size = lines.len().div_ceil(threads.max(1)).max(1);
+    let results: Vec<Result<Columns, String>> = thread::scope(|scope| {
+        let handles: Vec<_> =
+            lines.chunks(size)
+                 .enumerate()
+                 .map(|(i, chunk)| scope.spawn(move || {
+                     let mut cols = Columns::new(pad);
+                     cols.cs = (0..width).map(|_| Counter::new()).collect();
+                     cols.lines = i * size;
+                     for line in chunk {
+                         cols.add_line(line)?
+                     }
+                     Ok(cols)
+                 }))
+                 .collect();
+        handles.into_iter().map(|h| h.join().expect("counting thread panicked")).collect()
+    });
+
+    let mut total = Columns::new(pad);
+    for r in results {
+        total.merge(r?)
+    }
+    total.lines = lines.len();
+    Ok(total)
+}
+
+#[cfg(test)]
+static TEST_INPUT: &str = "eedadn\ndrvtee\neandsr\nraavrd\natevrs\ntsrnev\nsdttsa\nrasrtv\nnssdts\nntnada\nsvetve\ntesnvt\nvntsnd\nvrdear\ndvrsen\nenarar";
+
+#[test]
+fn aoc06_test_decode() {
+    use std::io::Cursor;
+    let cols = read_columns(&mut Cursor::new(TEST_INPUT), false, &mut |_| ()).unwrap();
+    assert_eq!(cols.decode(Counter::top), "easter");
+    assert_eq!(cols.decode(Counter::bottom), "advent");
+}
+
+#[test]
+fn aoc06_test_width() {
+    use std::io::Cursor;
+    let cols = read_columns(&mut Cursor::new("abcdefghij\nabcdefghij\nabcdefghiz"), false, &mut |_| ()).unwrap();
+    assert_eq!(cols.decode(Counter::top), "abcdefghij");
+
+    let err = read_columns(&mut Cursor::new("abc\nabc\nab"), false, &mut |_| ()).unwrap_err();
+    assert_eq!(err, "line 3: expected 3 characters, got 2");
+    let cols = read_columns(&mut Cursor::new("abc\nab\nxbcd\naxcd"), true, &mut |_| ()).unwrap();
+    assert_eq!(cols.decode(Counter::top), "abcd");
+}
+
+#[test]
+fn aoc06_test_n_best() {
+    use std::io::Cursor;
+    let cols = read_columns(&mut Cursor::new(TEST_INPUT), false, &mut |_| ()).unwrap();
+    let best = cols.n_best(Pick::Most, 3);
+    assert_eq!(best.len(), 3);
+    assert_eq!(best[0].message, "easter");
+    assert!(best.windows(2).all(|w| w[0].confidence() >= w[1].confidence()));
+    assert_eq!(cols.n_best(Pick::Least, 1)[0].message, "advent");
+
+    // The first column is a tie, which is flagged and broken alphabetically
+    // rather than by hash order.
+    let cols = read_columns(&mut Cursor::new("ax
+bx
+by
+ay"), false, &mut |_| ()).unwrap();
+    let best = cols.n_best(Pick::Most, 4);
+    let messages: Vec<&str> = best.iter().map(|c| c.message.as_str()).collect();
+    assert_eq!(messages, vec!["ax", "ay", "bx", "by"]);
+    assert!(best.iter().all(|c| c.tied == vec![true, true]));
+    assert_eq!(best[0].columns, vec![0.5, 0.5]);
+    assert_eq!(best[0].to_string(), "ax 2.500e-1 0.50* 0.50*");
+    assert_eq!(cols.decode(Counter::top), "ax");
+
+    let cols = read_columns(&mut Cursor::new("ab
+aa
+ca"), false, &mut |_| ()).unwrap();
+    let least = &cols.n_best(Pick::Least, 1)[0];
+    assert_eq!(least.message, "cb");
+    assert!(!least.is_tied());
+    assert!((least.columns[0] - 2.0 / 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn aoc06_test_counter() {
+    let mut c = Counter::new();
+    for ch in "abracadabra\u{e9}\u{e9}".chars() {
+        c.add_char(ch)
+    }
+    assert_eq!((c.top(), c.bottom()), ('a', 'c'));
+    assert_eq!(c.ranked(Pick::Most), vec![('a', 5), ('b', 2), ('r', 2), ('\u{e9}', 2), ('c', 1), ('d', 1)]);
+
+    let mut d = Counter::new();
+    for ch in "\u{e9}\u{e9}\u{e9}\u{e9}ddd".chars() {
+        d.add_char(ch)
+    }
+    c.merge(&d);
+    assert_eq!((c.top(), c.bottom()), ('\u{e9}', 'c'));
+}
+
+#[test]
+fn aoc06_test_parallel() {
+    use std::io::Cursor;
+    let seq = read_columns(&mut Cursor::new(TEST_INPUT), false, &mut |_| ()).unwrap();
+    for threads in 1..6 {
+        let par = count_parallel(TEST_INPUT, false, threads).unwrap();
+        assert_eq!(par.lines, 16);
+        assert_eq!(par.decode(Counter::top), seq.decode(Counter::top));
+        assert_eq!(par.decode(Counter::bottom), seq.decode(Counter::bottom));
+        assert_eq!(par.n_best(Pick::Most, 3), seq.n_best(Pick::Most, 3));
+    }
+
+    assert_eq!(count_parallel("abc\nabc\nabc\nab", false, 2).unwrap_err(), "line 4: expected 3 characters, got 2");
+    assert_eq!(count_parallel("ab\nab\nabcd\nxbc", true, 2).unwrap().decode(Counter::top), "abcd");
+    assert_eq!(count_parallel("", false, 3).unwrap().decode(Counter::top), "");
+}
+
+/// Counts all of stdin on `threads` threads, for large inputs where the
+/// message along the way isn't needed.
+fn solve_parallel(pad: bool, pick: Pick, best: Option<usize>, threads: usize) {
+    let mut text = String::new();
+    if let Err(e) = std::io::stdin().read_to_string(&mut text) {
+        return println!("error: {}", e)
+    }
+    match count_parallel(&text, pad, threads) {
+        Ok(cols) => print_answer(&cols, pick, best),
+        Err(e) => println!("error: {}", e)
+    }
+}
+
+/// What gets printed while the input is read.  The final message is
+/// always printed at the end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Output {
+    /// Nothing until the end.
+    Final,
+    /// The message so far, redrawn in place on one line.  Only used when
+    /// stdout is a terminal.
+    Watch,
+    /// The message so far after every `n` lines.
+    Every(usize)
+}
+
+/// How often `--watch` redraws.
+const FRAME: Duration = Duration::from_millis(50);
+
+fn solve(pad: bool, pick: Pick, best: Option<usize>, output: Output) {
     let stdin = std::io::stdin();
-    let mut cs: Vec<Counter> = (0..8).map(|_| Counter::new()).collect();
+    let decode = pick.decoder();
+    let mut drawn: Option<Instant> = None;
+    let result = read_columns(&mut stdin.lock(), pad, &mut |cols| match output {
+        Output::Final => (),
+        Output::Watch => {
+            if drawn.is_none_or(|d| d.elapsed() >= FRAME) {
+                print!("\r{}\x1b[K", cols.decode(decode));
+                let _ = std::io::stdout().flush();
+                drawn = Some(Instant::now())
+            }
+        }
+        Output::Every(n) => {
+            if cols.lines.is_multiple_of(n) {
+                println!("{}: {}", cols.lines, cols.decode(decode))
+            }
+        }
+    });
+    if output == Output::Watch && drawn.is_some() {
+        print!("\r\x1b[K")
+    }
+    match result {
+        Ok(cols) => print_answer(&cols, pick, best),
+        Err(e) => println!("error: {}", e)
+    }
+}
 
-    for line in stdin.lock().lines() {
-        for (i, ch) in line.unwrap().chars().enumerate() {
-            cs[i].add_char(ch)
+/// Prints the message and, if `best` is given, that many ranked candidates.
+fn print_answer(cols: &Columns, pick: Pick, best: Option<usize>) {
+    let decode = pick.decoder();
+    println!("{}", cols.decode(decode));
+
+    if let Some(k) = best {
+        let cands = cols.n_best(pick, k);
+        for (i, c) in cands.iter().enumerate() {
+            println!("{}. {}", i + 1, c)
+        }
+        if cands.iter().any(|c| c.is_tied()) {
+            println!("* tied with another character")
         }
-        println!("{}", cs.iter().map(|c| c.bottom()).collect::<String>());
     }
 }
 
 fn main () {
-    let flag = std::env::args().nth(1).unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let flag = args.first().expect("expected '-1' or '-2'");
+    let pad = args.iter().any(|a| a == "--pad");
+    let number = |name: &str| args.iter()
+                                  .position(|a| a == name)
+                                  .map(|i| args.get(i + 1).and_then(|k| k.parse().ok()).filter(|&k: &usize| k > 0));
+    let best = match number("-n") {
+        Some(Some(k)) => Some(k),
+        Some(None) => return println!("expected a number of messages after '-n'"),
+        None => None
+    };
+    let output = match (args.iter().any(|a| a == "--watch"), number("--every")) {
+        (true, Some(_)) => return println!("expected only one of '--watch' and '--every'"),
+        (true, None) => Output::Watch,
+        (false, Some(Some(n))) => Output::Every(n),
+        (false, Some(None)) => return println!("expected a number of lines after '--every'"),
+        (false, None) => Output::Final
+    };
+    let threads = match number("-j") {
+        Some(Some(_)) if output != Output::Final => return println!("'-j' only prints the final message"),
+        Some(Some(j)) => Some(j),
+        Some(None) => return println!("expected a number of threads after '-j'"),
+        None => None
+    };
+    let output = if output == Output::Watch && !std::io::stdout().is_terminal() { Output::Final } else { output };
 
-    match flag.as_ref() {
-        "-1" => part_one(),
-        "-2" => part_two(),
-        _ => println!("expected '-1' or '-2'")
+    let pick = match flag.as_ref() {
+        "-1" => Pick::Most,
+        "-2" => Pick::Least,
+        _ => return println!("expected '-1' or '-2'")
+    };
+    match threads {
+        Some(j) => solve_parallel(pad, pick, best, j),
+        None => solve(pad, pick, best, output)
     }
 }